@@ -0,0 +1,42 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ethereum_proofs::{KeccakHasher, KeccakState};
+use hash_db::Hasher;
+
+fn slots() -> Vec<[u8; 32]> {
+    (0..10_000u64)
+        .map(|index| {
+            let mut slot = [0u8; 32];
+            slot[24..].copy_from_slice(&index.to_be_bytes());
+            slot
+        })
+        .collect()
+}
+
+fn hash_slots(c: &mut Criterion) {
+    let slots = slots();
+    let mut group = c.benchmark_group("hash 10k slots");
+
+    group.bench_function("KeccakHasher::hash", |b| {
+        b.iter(|| {
+            for slot in slots.iter() {
+                black_box(KeccakHasher::hash(black_box(slot)));
+            }
+        })
+    });
+
+    group.bench_function("KeccakState::hash_into", |b| {
+        let state = KeccakState::new();
+        let mut out = [0u8; 32];
+        b.iter(|| {
+            for slot in slots.iter() {
+                state.hash_into(black_box(slot), &mut out);
+                black_box(&out);
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, hash_slots);
+criterion_main!(benches);
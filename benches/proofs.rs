@@ -0,0 +1,112 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ethereum_proofs::state::verify_storage_proof_value;
+use ethereum_proofs::types::{rlp_encode_storage_value, AccountState, B256, U256};
+use ethereum_proofs::{
+    empty_db, generate_proof, verify_proof_raw_key, EthereumLayout, EthereumMemoryDB,
+};
+use primitive_types::H160;
+use trie_db::{SecTrieDBMut, TrieMut};
+
+/// Number of distinct keys proven in each iteration, so that throughput is in proofs per second.
+const PROOFS_PER_ITER: usize = 100;
+
+fn build_trie(entries: &[(Vec<u8>, Vec<u8>)]) -> (EthereumMemoryDB, B256) {
+    let mut db = empty_db();
+    let mut root = B256::default();
+    {
+        let mut trie = SecTrieDBMut::<EthereumLayout>::new(&mut db, &mut root);
+        for (key, value) in entries.iter() {
+            trie.insert(key, value).unwrap();
+        }
+    }
+    (db, root)
+}
+
+fn accounts(count: u64) -> Vec<(Vec<u8>, Vec<u8>)> {
+    (0..count)
+        .map(|index| {
+            let account = AccountState {
+                nonce: index,
+                balance: U256::from(index) * U256::from(1_000_000_000u64),
+                storage_hash: ethereum_proofs::EMPTY_TRIE_ROOT,
+                code_hash: B256::repeat_byte(0x11),
+            };
+            (H160::from_low_u64_be(index + 1).as_bytes().to_vec(), account.rlp_encode())
+        })
+        .collect()
+}
+
+fn slots(count: u64) -> Vec<(Vec<u8>, Vec<u8>)> {
+    (0..count)
+        .map(|index| {
+            let slot = B256::from(U256::from(index).to_be_bytes::<32>());
+            (slot.to_vec(), rlp_encode_storage_value(&U256::from(index + 1)))
+        })
+        .collect()
+}
+
+/// The keys proven in each iteration, spread over the trie.
+fn sample<T: Clone>(entries: &[T]) -> Vec<T> {
+    let step = (entries.len() / PROOFS_PER_ITER).max(1);
+    entries.iter().step_by(step).take(PROOFS_PER_ITER).cloned().collect()
+}
+
+fn account_proofs(c: &mut Criterion) {
+    for count in [100, 1_000, 10_000] {
+        let entries = accounts(count);
+        let (db, root) = build_trie(&entries);
+        let sampled = sample(&entries);
+        let proofs: Vec<_> = sampled
+            .iter()
+            .map(|(key, _)| generate_proof::<EthereumLayout>(&db, &root, key).unwrap().0)
+            .collect();
+
+        let mut group = c.benchmark_group("account proofs");
+        group.throughput(Throughput::Elements(sampled.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("generate_proof", count), &sampled, |b, sampled| {
+            b.iter(|| {
+                for (key, _) in sampled.iter() {
+                    black_box(generate_proof::<EthereumLayout>(&db, &root, key).unwrap());
+                }
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("verify_proof", count), &sampled, |b, sampled| {
+            b.iter(|| {
+                for ((key, value), proof) in sampled.iter().zip(proofs.iter()) {
+                    verify_proof_raw_key::<EthereumLayout>(&root, proof, key, Some(value)).unwrap();
+                }
+            })
+        });
+
+        group.finish();
+    }
+}
+
+fn storage_proofs(c: &mut Criterion) {
+    let entries = slots(1_000);
+    let (db, root) = build_trie(&entries);
+    let sampled = sample(&entries);
+    let proofs: Vec<_> = sampled
+        .iter()
+        .map(|(slot, _)| {
+            let proof = generate_proof::<EthereumLayout>(&db, &root, slot).unwrap().0;
+            (B256::from_slice(slot), proof)
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("storage proofs");
+    group.throughput(Throughput::Elements(proofs.len() as u64));
+    group.bench_function("verify_storage_proof_value 1000", |b| {
+        b.iter(|| {
+            for (slot, proof) in proofs.iter() {
+                black_box(verify_storage_proof_value::<EthereumLayout>(&root, proof, slot).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, account_proofs, storage_proofs);
+criterion_main!(benches);
@@ -0,0 +1,16 @@
+#![no_main]
+
+use ethereum_proofs::{node_codec::RlpNodeCodec, KeccakHasher};
+use libfuzzer_sys::fuzz_target;
+use trie_db::NodeCodec;
+
+/// Decode arbitrary bytes as a trie node. Decoding must only ever return `Ok` or `Err`, and the
+/// ranges of a successfully decoded plan must be within `data`, which building the node checks by
+/// slicing them.
+fn fuzz_decode_plan(data: &[u8]) {
+    if let Ok(plan) = RlpNodeCodec::<KeccakHasher>::decode_plan(data) {
+        let _ = plan.build(data);
+    }
+}
+
+fuzz_target!(|data: &[u8]| fuzz_decode_plan(data));
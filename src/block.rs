@@ -0,0 +1,216 @@
+//! Proofs in the transaction and receipt tries of a block, which are keyed by the RLP of the
+//! transaction index instead of by a hash.
+
+use crate::rstd::vec::Vec;
+use crate::types::{decode_hash, B256};
+use crate::{verify_proof_hashed_key, VerifyError};
+
+use rlp::{DecoderError, Rlp};
+use trie_db::{CError, TrieHash, TrieLayout};
+
+/// Index of `stateRoot` among the fields of a block header.
+const STATE_ROOT_INDEX: usize = 3;
+
+/// The key of the transaction or receipt at `index` in the tries of a block: `rlp(index)`, which
+/// is not hashed.
+pub fn tx_trie_key(index: u64) -> Vec<u8> {
+    rlp::encode(&index).to_vec()
+}
+
+/// Verify the proof of the transaction at `tx_index` in the transactions trie at `tx_root`.
+/// `expected_tx_rlp` is the transaction as stored in the trie: its RLP for legacy transactions,
+/// and the type byte followed by the RLP of the payload for typed transactions.
+pub fn verify_tx_proof<L>(
+    tx_root: &TrieHash<L>,
+    proof: &[Vec<u8>],
+    tx_index: u64,
+    expected_tx_rlp: &[u8],
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    verify_proof_hashed_key::<L>(tx_root, proof, &tx_trie_key(tx_index), Some(expected_tx_rlp))
+}
+
+/// Verify the proof of the receipt of the transaction at `tx_index` in the receipts trie at
+/// `receipts_root`. As for transactions, typed receipts are stored as the type byte followed by the
+/// RLP of the payload.
+pub fn verify_receipt_proof<L>(
+    receipts_root: &TrieHash<L>,
+    proof: &[Vec<u8>],
+    tx_index: u64,
+    expected_receipt_rlp: &[u8],
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    verify_proof_hashed_key::<L>(
+        receipts_root,
+        proof,
+        &tx_trie_key(tx_index),
+        Some(expected_receipt_rlp),
+    )
+}
+
+/// Extract the `stateRoot` of a block header from its RLP, so that proofs can be verified against
+/// a header whose hash is trusted rather than against a state root supplied by the caller. Only
+/// the header list and the state root are decoded, so headers of every fork are accepted.
+pub fn extract_state_root_from_header(header_rlp: &[u8]) -> Result<B256, DecoderError> {
+    let rlp = Rlp::new(header_rlp);
+    if !rlp.is_list() {
+        return Err(DecoderError::RlpExpectedToBeList);
+    }
+    if rlp.payload_info()?.total() != header_rlp.len() {
+        return Err(DecoderError::RlpInconsistentLengthAndData);
+    }
+    decode_hash(&rlp.at(STATE_ROOT_INDEX)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::keccak256;
+    use crate::{empty_db, generate_proof_for_key, EthereumLayout, EthereumMemoryDB};
+
+    use rlp::RlpStream;
+    use trie_db::{TrieDBMutBuilder, TrieMut};
+
+    fn item(index: u64, byte: u8) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&index);
+        stream.append(&vec![byte; 32]);
+        stream.out().to_vec()
+    }
+
+    /// A trie of [`item`]s keyed by index, as the transactions or receipts trie of a block.
+    fn block_trie(len: u64, byte: u8) -> (EthereumMemoryDB, B256) {
+        let mut db = empty_db();
+        let mut root = Default::default();
+        {
+            let mut trie = TrieDBMutBuilder::<EthereumLayout>::new(&mut db, &mut root).build();
+            for index in 0..len {
+                trie.insert(&tx_trie_key(index), &item(index, byte)).unwrap();
+            }
+        }
+        (db, root)
+    }
+
+    fn hash(hex: &str) -> B256 {
+        B256::from_slice(&hex::decode(hex).unwrap())
+    }
+
+    /// The header of the mainnet genesis block.
+    fn genesis_header() -> Vec<u8> {
+        let empty_trie_root =
+            hash("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+        let mut stream = RlpStream::new_list(15);
+        stream.append(&B256::ZERO.as_slice());
+        stream.append(
+            &hash("1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347").as_slice(),
+        );
+        stream.append(&[0u8; 20].as_slice());
+        stream.append(
+            &hash("d7f8974fb5ac78d9ac099b9ad5018bedc2ce0a72dad1827a1709da30580f0544").as_slice(),
+        );
+        stream.append(&empty_trie_root.as_slice());
+        stream.append(&empty_trie_root.as_slice());
+        stream.append(&[0u8; 256].as_slice());
+        stream.append(&0x4_0000_0000u64);
+        stream.append(&0u64);
+        stream.append(&5000u64);
+        stream.append(&0u64);
+        stream.append(&0u64);
+        stream.append(
+            &hash("11bbe8db4e347b4e8c937c1c8370e4b5ed33adb3db69cbdb7a38e1e50b1b82fa").as_slice(),
+        );
+        stream.append(&B256::ZERO.as_slice());
+        stream.append(&[0, 0, 0, 0, 0, 0, 0, 0x42u8].as_slice());
+        stream.out().to_vec()
+    }
+
+    #[test]
+    fn it_extracts_state_root_of_mainnet_genesis_header() {
+        let header = genesis_header();
+        assert_eq!(
+            keccak256(&header),
+            hash("d4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3")
+        );
+        assert_eq!(
+            extract_state_root_from_header(&header),
+            Ok(hash("d7f8974fb5ac78d9ac099b9ad5018bedc2ce0a72dad1827a1709da30580f0544"))
+        );
+
+        assert_eq!(
+            extract_state_root_from_header(&rlp::encode(&header)),
+            Err(DecoderError::RlpExpectedToBeList)
+        );
+        let mut trailing = header.clone();
+        trailing.push(0x80);
+        assert_eq!(
+            extract_state_root_from_header(&trailing),
+            Err(DecoderError::RlpInconsistentLengthAndData)
+        );
+        let mut short = RlpStream::new_list(3);
+        short.append(&B256::ZERO.as_slice()).append(&B256::ZERO.as_slice()).append(&0u64);
+        assert!(extract_state_root_from_header(&short.out()).is_err());
+    }
+
+    #[test]
+    fn it_encodes_tx_trie_keys() {
+        assert_eq!(tx_trie_key(0), vec![0x80]);
+        assert_eq!(tx_trie_key(127), vec![0x7f]);
+        assert_eq!(tx_trie_key(128), vec![0x81, 0x80]);
+        assert_eq!(tx_trie_key(256), vec![0x82, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn it_verifies_tx_proofs() {
+        let (db, root) = block_trie(130, 0xab);
+        let expected: B256 = "0xd79e92f5c2f65d3ccc759a47621135cd3828a50bf9f38b3f28f3b3fd68112e52"
+            .parse()
+            .unwrap();
+        assert_eq!(root, expected);
+
+        for index in [0, 1, 127, 128, 129] {
+            let (proof, value) =
+                generate_proof_for_key::<EthereumLayout>(&db, &root, &tx_trie_key(index)).unwrap();
+            let tx = item(index, 0xab);
+            assert_eq!(value.as_ref(), Some(&tx));
+            verify_tx_proof::<EthereumLayout>(&root, &proof, index, &tx)
+                .expect("Failed to verify transaction proof");
+            let other = item(index + 1, 0xab);
+            assert!(verify_tx_proof::<EthereumLayout>(&root, &proof, index, &other).is_err());
+        }
+    }
+
+    #[test]
+    fn it_verifies_proofs_in_single_tx_trie() {
+        // The only key is 1-byte `0x80`, the path of the root leaf.
+        let (db, root) = block_trie(1, 0xab);
+        let (proof, _) =
+            generate_proof_for_key::<EthereumLayout>(&db, &root, &tx_trie_key(0)).unwrap();
+        assert_eq!(proof.len(), 1);
+        verify_tx_proof::<EthereumLayout>(&root, &proof, 0, &item(0, 0xab))
+            .expect("Failed to verify sole transaction");
+        for index in [1, 128] {
+            verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &tx_trie_key(index), None)
+                .expect("Failed to verify absence of transaction");
+            assert!(verify_tx_proof::<EthereumLayout>(&root, &proof, index, &item(0, 0xab)).is_err());
+        }
+    }
+
+    #[test]
+    fn it_verifies_receipt_proofs() {
+        let (db, root) = block_trie(3, 0xcd);
+        let expected: B256 = "0xc4779a709b2f7c690b3c7cf51421a8501f863c48a8063e2306a1fc7852d447c7"
+            .parse()
+            .unwrap();
+        assert_eq!(root, expected);
+
+        let (proof, _) =
+            generate_proof_for_key::<EthereumLayout>(&db, &root, &tx_trie_key(2)).unwrap();
+        verify_receipt_proof::<EthereumLayout>(&root, &proof, 2, &item(2, 0xcd))
+            .expect("Failed to verify receipt proof");
+        assert!(verify_receipt_proof::<EthereumLayout>(&root, &proof, 1, &item(1, 0xcd)).is_err());
+    }
+}
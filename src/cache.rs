@@ -0,0 +1,197 @@
+//! Memoization of successful proof verifications, for services that verify the same proofs again
+//! and again, such as the same witness across concurrent requests. Only successes are cached, so a
+//! proof that fails is walked every time and reports its error.
+
+use crate::types::B256;
+use crate::{verify_proof_raw_key, VerifyError};
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tiny_keccak::{Hasher as _, Keccak};
+use trie_db::{CError, TrieHash, TrieLayout};
+
+/// A bounded cache of verified proofs, evicting the least recently used entry once it holds
+/// `capacity` of them. It can be shared between threads.
+pub struct ProofCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct Entries {
+    /// The last use of each entry.
+    last_used: HashMap<B256, u64>,
+    /// The entries by last use, oldest first.
+    by_use: BTreeMap<u64, B256>,
+    tick: u64,
+}
+
+impl Entries {
+    fn touch(&mut self, digest: B256) {
+        self.tick += 1;
+        if let Some(previous) = self.last_used.insert(digest, self.tick) {
+            self.by_use.remove(&previous);
+        }
+        self.by_use.insert(self.tick, digest);
+    }
+}
+
+impl ProofCache {
+    /// An empty cache holding at most `capacity` verified proofs.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(Entries::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Verify the proof of `raw_key` in the trie at `root` as [`verify_proof_raw_key`], unless the
+    /// same root, proof, key and expected value have been verified before and are still cached.
+    pub fn verify_cached<L>(
+        &self,
+        root: &TrieHash<L>,
+        proof: &[Vec<u8>],
+        raw_key: &[u8],
+        expected_value: Option<&[u8]>,
+    ) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+    where
+        L: TrieLayout,
+    {
+        let digest = digest::<L>(root, proof, raw_key, expected_value);
+        {
+            let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+            if entries.last_used.contains_key(&digest) {
+                entries.touch(digest);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        verify_proof_raw_key::<L>(root, proof, raw_key, expected_value)?;
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        entries.touch(digest);
+        while entries.last_used.len() > self.capacity {
+            if let Some((_, oldest)) = entries.by_use.pop_first() {
+                entries.last_used.remove(&oldest);
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of verified proofs held.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|err| err.into_inner()).last_used.len()
+    }
+
+    /// Whether no verified proof is held.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of verifications answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// The number of verifications that walked the proof.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+/// The Keccak of everything a verification depends on. Each part is prefixed with its length, so
+/// that no two different inputs are hashed as the same bytes.
+fn digest<L: TrieLayout>(
+    root: &TrieHash<L>,
+    proof: &[Vec<u8>],
+    raw_key: &[u8],
+    expected_value: Option<&[u8]>,
+) -> B256 {
+    let mut keccak = Keccak::v256();
+    let mut update = |bytes: &[u8]| {
+        keccak.update(&(bytes.len() as u64).to_be_bytes());
+        keccak.update(bytes);
+    };
+    update(root.as_ref());
+    update(raw_key);
+    match expected_value {
+        Some(value) => {
+            update(&[1]);
+            update(value);
+        }
+        None => update(&[0]),
+    }
+    update(&(proof.len() as u64).to_be_bytes());
+    for node in proof.iter() {
+        update(node);
+    }
+    let mut output = [0u8; 32];
+    keccak.finalize(&mut output);
+    B256::from(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_trie;
+    use crate::{generate_proof, EthereumLayout};
+
+    #[test]
+    fn it_answers_repeated_verification_from_cache() {
+        let entries: Vec<_> = (0..8u8).map(|i| (vec![i; 20], vec![i; 40])).collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[2].0).unwrap();
+        let cache = ProofCache::new(2);
+
+        for _ in 0..3 {
+            cache
+                .verify_cached::<EthereumLayout>(&root, &proof, &entries[2].0, Some(&entries[2].1))
+                .expect("Failed to verify proof");
+        }
+        assert_eq!((cache.misses(), cache.hits()), (1, 2));
+
+        // A different key or value under the same proof is verified, and fails, every time.
+        for _ in 0..2 {
+            assert!(cache
+                .verify_cached::<EthereumLayout>(&root, &proof, &entries[3].0, Some(&entries[2].1))
+                .is_err());
+            assert!(cache
+                .verify_cached::<EthereumLayout>(&root, &proof, &entries[2].0, None)
+                .is_err());
+        }
+        assert_eq!((cache.misses(), cache.hits()), (5, 2));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn it_evicts_least_recently_used_proof() {
+        let entries: Vec<_> = (0..8u8).map(|i| (vec![i; 20], vec![i; 40])).collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let cache = ProofCache::new(2);
+        let verify = |index: usize| {
+            let (key, value) = &entries[index];
+            let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, key).unwrap();
+            cache.verify_cached::<EthereumLayout>(&root, &proof, key, Some(value)).unwrap();
+        };
+
+        verify(0);
+        verify(1);
+        verify(0);
+        verify(2);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.misses(), 3);
+        verify(0);
+        assert_eq!(cache.misses(), 3);
+        verify(1);
+        assert_eq!(cache.misses(), 4);
+    }
+}
@@ -1,34 +1,83 @@
 use crate::node_codec;
-use crate::rstd::{result::Result, vec::Vec};
+use crate::rstd::{result::Result, vec::Vec, BTreeMap, BTreeSet};
 
 use core::marker::PhantomData;
+use core::ops::ControlFlow;
 use hash_db::Hasher;
 use trie_db::{
-    node::{decode_hash, Node, NodeHandle, Value},
-    CError, NibbleSlice, NodeCodec, TrieHash,
+    node::{decode_hash, Node, NodeHandle, NodePlan, Value},
+    CError, ChildReference, NibbleSlice, NodeCodec, TrieHash,
     TrieLayout,
 };
 
 #[derive(Default, Clone)]
 pub struct RlpTrieLayout<H>(PhantomData<H>);
 
+/// [`RlpTrieLayout`] for the compact proofs of `trie_db::proof`, such as those re-encoded by
+/// [`crate::to_trie_db_proof`]. Its codec also encodes and decodes the child such a proof omits,
+/// which is not valid in an Ethereum trie, so it is only meant for `trie_db::proof`.
+#[derive(Default, Clone)]
+pub struct TrieDbProofLayout<H>(PhantomData<H>);
+
+/// The deepest a node may be on a proof path before the proof is rejected. Every node but the last
+/// consumes at least one nibble of the key, so a path for a 32-byte key has at most 65 nodes; the
+/// limit leaves a small margin over that.
+pub const MAX_PROOF_DEPTH: usize = 64 + 8;
+
+/// How strictly [`crate::verify_proof_with_options`] verifies a proof. The default verifies as
+/// [`crate::verify_proof_hashed_key`] does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyOptions {
+    /// The deepest a node may be on the proof path, [`MAX_PROOF_DEPTH`] by default. Deeper paths
+    /// fail with [`VerifyError::ProofTooDeep`].
+    pub max_depth: usize,
+    /// Whether to reject proofs holding nodes that are not on the path of the key, with
+    /// [`VerifyError::NonMinimalProof`]. Off by default, as extra nodes are otherwise ignored.
+    pub check_minimal: bool,
+    /// Whether to compare values of equal length in constant time. The default follows the
+    /// `ct-eq` feature.
+    pub constant_time: bool,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: MAX_PROOF_DEPTH,
+            check_minimal: false,
+            constant_time: DEFAULT_CONSTANT_TIME,
+        }
+    }
+}
+
+const DEFAULT_CONSTANT_TIME: bool = cfg!(feature = "ct-eq");
+
 impl<H: Hasher> TrieLayout for RlpTrieLayout<H> {
     const USE_EXTENSION: bool = true;
     const ALLOW_EMPTY: bool = false;
+    // Ethereum stores values inline in their leaf or branch whatever their size; only child nodes
+    // of 32 bytes or more are referenced by hash.
     const MAX_INLINE_VALUE: Option<u32> = None;
     type Hash = H;
     type Codec = node_codec::RlpNodeCodec<H>;
 }
 
+impl<H: Hasher> TrieLayout for TrieDbProofLayout<H> {
+    const USE_EXTENSION: bool = true;
+    const ALLOW_EMPTY: bool = false;
+    const MAX_INLINE_VALUE: Option<u32> = None;
+    type Hash = H;
+    type Codec = node_codec::TrieDbProofCodec<H>;
+}
+
 /// Errors that may occur during proof verification. Most of the errors types simply indicate that
 /// the proof is invalid with respect to the statement being verified, and the exact error type can
 /// be used for debugging.
 #[derive(PartialEq, Eq)]
 #[cfg_attr(feature = "std", derive(Debug))]
-pub enum VerifyError<'a, HO, CE> {
+pub enum VerifyError<HO, CE> {
     /// The proof does not contain any value for the given key
     /// the error carries the nibbles left after traversing the trie
-    NonExistingValue(NibbleSlice<'a>),
+    NonExistingValue(Vec<u8>),
     /// The proof contains a value for the given key
     /// while we were expecting to find a non-existence proof
     ExistingValue(Vec<u8>),
@@ -37,17 +86,48 @@ pub enum VerifyError<'a, HO, CE> {
     ValueMismatch(Vec<u8>),
     /// The proof is missing trie nodes required to verify.
     IncompleteProof,
-    /// The node hash computed from the proof is not matching.
-    HashMismatch(HO),
-    /// One of the proof nodes could not be decoded.
-    DecodeError(CE),
+    /// The node hash computed from the proof is not matching. `depth` is the number of nodes above
+    /// the offending one on the path, which is its index in a proof supplied in path order as
+    /// inline nodes never have hash-referenced descendants.
+    HashMismatch { hash: HO, depth: usize },
+    /// The first node of the proof hashes to `found` instead of to the `expected` root.
+    RootMismatch { expected: HO, found: HO },
+    /// The proof node at `depth`, counted as for [`VerifyError::HashMismatch`], is not a valid
+    /// RLP trie node, or a value it holds could not be decoded.
+    DecodeError { depth: usize, source: CE },
     /// Error in converting a plain hash into a HO
-    HashDecodeError(&'a [u8]),
+    HashDecodeError(Vec<u8>),
+    /// The node expected next on the path is in the proof, but not at the position the walk
+    /// reached. Proof nodes must be supplied in path order, from the root down.
+    NodesOutOfOrder,
+    /// The proof does not verify against any of the candidate roots.
+    NoMatchingRoot,
+    /// The path through the proof is deeper than [`MAX_PROOF_DEPTH`], which no valid proof is, or
+    /// than the [`VerifyOptions::max_depth`] verified with.
+    ProofTooDeep,
+    /// One of the inputs to [`crate::verify_proof_hex`] is not valid hex, or the root is not the
+    /// length of a hash.
+    HexDecode(hex::FromHexError),
+    /// A storage proof given to [`crate::state::verify_linked_proof`] is rooted at `found` instead
+    /// of at the `storage_hash` of the proven account.
+    StorageRootMismatch { expected: HO, found: HO },
+    /// A value was found in the value slot of a branch while verifying a key of fixed length, such
+    /// as an account key, whose values can only be stored in leaves.
+    ValueInBranchForFixedKey,
+    /// The account proven by [`crate::state::verify_nonce_proof`] has nonce `found` instead of
+    /// `expected`.
+    NonceMismatch { expected: u64, found: u64 },
+    /// The proof holds nodes that are not on the path of the key, which
+    /// [`VerifyOptions::check_minimal`] rejects.
+    NonMinimalProof,
+    /// The node with this hash is referenced from more than one place in the nodes walked by
+    /// [`crate::leaves_in_proof`]. A crafted proof can use that to make the walk grow
+    /// exponentially with the number of nodes.
+    NodeReachedTwice(HO),
 }
 
-#[cfg(feature = "std")]
-impl<'a, HO: std::fmt::Debug, CE: std::error::Error> std::fmt::Display for VerifyError<'a, HO, CE> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+impl<HO: core::fmt::Debug, CE: core::fmt::Display> core::fmt::Display for VerifyError<HO, CE> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
         match self {
             VerifyError::NonExistingValue(key) => {
                 write!(f, "Key does not exist in trie: remaining key={:?}", key)
@@ -59,8 +139,17 @@ impl<'a, HO: std::fmt::Debug, CE: std::error::Error> std::fmt::Display for Verif
                 write!(f, "Expected value was not found in the trie: key={:?}", key)
             }
             VerifyError::IncompleteProof => write!(f, "Proof is incomplete -- expected more nodes"),
-            VerifyError::HashMismatch(hash) => write!(f, "hash mismatch found: hash={:?}", hash),
-            VerifyError::DecodeError(err) => write!(f, "Unable to decode proof node: {}", err),
+            VerifyError::HashMismatch { hash, depth } => {
+                write!(f, "hash mismatch found: hash={:?} depth={}", hash, depth)
+            }
+            VerifyError::RootMismatch { expected, found } => write!(
+                f,
+                "Proof root node does not hash to the root: expected={:?} found={:?}",
+                expected, found
+            ),
+            VerifyError::DecodeError { depth, source } => {
+                write!(f, "Unable to decode proof node: {} depth={}", source, depth)
+            }
             VerifyError::HashDecodeError(plain_hash) => {
                 write!(
                     f,
@@ -68,215 +157,980 @@ impl<'a, HO: std::fmt::Debug, CE: std::error::Error> std::fmt::Display for Verif
                     plain_hash
                 )
             }
+            VerifyError::NodesOutOfOrder => {
+                write!(f, "Proof nodes are not in path order, root node first")
+            }
+            VerifyError::NoMatchingRoot => write!(f, "Proof does not match any candidate root"),
+            VerifyError::ProofTooDeep => write!(f, "Proof path is deeper than the depth limit"),
+            VerifyError::HexDecode(err) => write!(f, "Unable to decode hex input: {}", err),
+            VerifyError::StorageRootMismatch { expected, found } => write!(
+                f,
+                "Storage proof root does not match account storage hash: expected={:?} found={:?}",
+                expected, found
+            ),
+            VerifyError::ValueInBranchForFixedKey => {
+                write!(f, "Value found in a branch node for a fixed-length key")
+            }
+            VerifyError::NonceMismatch { expected, found } => {
+                write!(f, "Account nonce mismatch: expected={} found={}", expected, found)
+            }
+            VerifyError::NonMinimalProof => {
+                write!(f, "Proof holds nodes that are not on the path of the key")
+            }
+            VerifyError::NodeReachedTwice(hash) => {
+                write!(f, "Proof node is reached more than once: hash={:?}", hash)
+            }
         }
     }
 }
 
 #[cfg(feature = "std")]
-impl<'a, HO: std::fmt::Debug, CE: std::error::Error + 'static> std::error::Error
-    for VerifyError<'a, HO, CE>
+impl<HO: std::fmt::Debug, CE: std::error::Error + 'static> std::error::Error
+    for VerifyError<HO, CE>
 {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            VerifyError::DecodeError(err) => Some(err),
+            VerifyError::DecodeError { source, .. } => Some(source),
             _ => None,
         }
     }
 }
 
-pub(crate) fn process_node<'a, L>(
-    expected_node_hash: Option<&<L::Hash as Hasher>::Out>,
-    encoded_node: &'a [u8],
-    key: NibbleSlice<'a>,
-    expected_value: Option<&[u8]>,
-    proof: &'a [Vec<u8>],
-) -> Result<(), VerifyError<'a, TrieHash<L>, CError<L>>>
+/// Compare an authenticated value against the expected one. With the `ct-eq` feature, values of
+/// equal length are compared in constant time, so verification does not leak how much of a secret
+/// expected value matched. Lengths are not treated as secret.
+fn value_eq(proven: &[u8], expected: &[u8]) -> bool {
+    values_eq(proven, expected, DEFAULT_CONSTANT_TIME)
+}
+
+/// Compare values as [`value_eq`] does, in constant time if `constant_time` is set.
+fn values_eq(proven: &[u8], expected: &[u8], constant_time: bool) -> bool {
+    if constant_time {
+        ct_eq(proven, expected)
+    } else {
+        proven == expected
+    }
+}
+
+#[cfg(feature = "ct-eq")]
+fn ct_eq(proven: &[u8], expected: &[u8]) -> bool {
+    use subtle::ConstantTimeEq;
+    proven.ct_eq(expected).into()
+}
+
+/// Without `subtle`, the bytes are all folded into one difference, hidden from the optimizer so
+/// that the comparison does not stop at the first differing byte.
+#[cfg(not(feature = "ct-eq"))]
+fn ct_eq(proven: &[u8], expected: &[u8]) -> bool {
+    if proven.len() != expected.len() {
+        return false;
+    }
+    let diff = proven.iter().zip(expected).fold(0u8, |diff, (a, b)| diff | (a ^ b));
+    core::hint::black_box(diff) == 0
+}
+
+/// Decode the proof node at `depth`, failing with [`VerifyError::DecodeError`] at that depth.
+fn decode_at<L: TrieLayout>(
+    encoded_node: &[u8],
+    depth: usize,
+) -> Result<Node<'_>, VerifyError<TrieHash<L>, CError<L>>> {
+    <L::Codec as NodeCodec>::decode(encoded_node)
+        .map_err(|source| VerifyError::DecodeError { depth, source })
+}
+
+/// Copy out the nibbles of the key left to traverse, for reporting in errors.
+fn remaining_nibbles(key: &NibbleSlice) -> Vec<u8> {
+    (0..key.len()).map(|i| key.at(i)).collect()
+}
+
+/// Index a set of proof nodes by their hash so the walk can resolve children regardless of the
+/// order the nodes were supplied in.
+pub(crate) fn index_nodes<L>(nodes: &[Vec<u8>]) -> BTreeMap<TrieHash<L>, &[u8]>
 where
     L: TrieLayout,
 {
-    if let Some(value) = expected_value {
-        if encoded_node == value {
-            return Ok(());
+    nodes
+        .iter()
+        .map(|node| (<L::Hash as Hasher>::hash(node), node.as_slice()))
+        .collect()
+}
+
+/// Walk from `root` along `key` through the indexed nodes and return every hash-referenced node
+/// visited on the way, root first. Inline nodes are part of their parent and are not returned
+/// separately. The walk stops where the key runs out or diverges from the trie, so the result is
+/// also a valid exclusion proof.
+pub(crate) fn collect_path<'a, L>(
+    root: &TrieHash<L>,
+    nodes: &BTreeMap<TrieHash<L>, &'a [u8]>,
+    mut key: NibbleSlice<'a>,
+) -> Result<Vec<&'a [u8]>, VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    let mut path = Vec::new();
+    let mut encoded_node = *nodes.get(root).ok_or(VerifyError::IncompleteProof)?;
+    path.push(encoded_node);
+    let mut depth = 0;
+    loop {
+        let node = decode_at::<L>(encoded_node, depth)?;
+        depth += 1;
+        let (nib, mut children) = match node {
+            Node::Empty | Node::Leaf(..) => return Ok(path),
+            Node::Extension(nib, handle) => {
+                if !key.starts_with(&nib) {
+                    return Ok(path);
+                }
+                key.advance(nib.len());
+                encoded_node = resolve_child::<L>(handle, nodes, &mut path)?;
+                continue;
+            }
+            Node::Branch(children, _) => (None, children),
+            Node::NibbledBranch(nib, children, _) => (Some(nib), children),
+        };
+        if let Some(nib) = nib {
+            if !key.starts_with(&nib) {
+                return Ok(path);
+            }
+            key.advance(nib.len());
+        }
+        if key.is_empty() {
+            return Ok(path);
         }
+        let handle = match children[key.at(0) as usize].take() {
+            Some(handle) => handle,
+            None => return Ok(path),
+        };
+        key.advance(1);
+        encoded_node = resolve_child::<L>(handle, nodes, &mut path)?;
     }
-    if let Some(expected) = expected_node_hash {
-        let calculated_node_hash = <L::Hash as Hasher>::hash(encoded_node);
-        if calculated_node_hash != *expected {
-            return Err(VerifyError::HashMismatch(calculated_node_hash));
+}
+
+/// Resolve a child handle to its encoded node, recording it in `path` when it is referenced by
+/// hash.
+fn resolve_child<'a, L>(
+    handle: NodeHandle<'a>,
+    nodes: &BTreeMap<TrieHash<L>, &'a [u8]>,
+    path: &mut Vec<&'a [u8]>,
+) -> Result<&'a [u8], VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    match handle {
+        NodeHandle::Inline(encoded_node) => Ok(encoded_node),
+        NodeHandle::Hash(plain_hash) => {
+            let hash = decode_hash::<L::Hash>(plain_hash)
+                .ok_or_else(|| VerifyError::HashDecodeError(plain_hash.to_vec()))?;
+            let encoded_node = *nodes.get(&hash).ok_or(VerifyError::IncompleteProof)?;
+            path.push(encoded_node);
+            Ok(encoded_node)
         }
     }
-    let node = <L::Codec as NodeCodec>::decode(encoded_node).map_err(VerifyError::DecodeError)?;
-    match node {
-        Node::Empty => process_empty::<L>(key, expected_value, proof),
-        Node::Leaf(nib, data) => process_leaf::<L>(nib, data, key, expected_value, proof),
-        Node::Extension(nib, handle) => {
-            process_extension::<L>(&nib, handle, key, expected_value, proof)
+}
+
+/// Walk every node reachable from `root` through the indexed nodes and return the full nibble path
+/// and value of each leaf, and of each branch holding a value, in key order. Children that are not
+/// among the nodes are skipped, so any subset of the trie can be walked. Nodes are only reached
+/// through their hash, which authenticates every returned value against `root`.
+///
+/// Each node is walked at most once, so the work is bounded by the size of the nodes: a hash
+/// referenced from a second place fails with [`VerifyError::NodeReachedTwice`], and a path deeper
+/// than [`MAX_PROOF_DEPTH`] with [`VerifyError::ProofTooDeep`].
+pub(crate) fn collect_leaves<'a, L>(
+    root: &TrieHash<L>,
+    nodes: &BTreeMap<TrieHash<L>, &'a [u8]>,
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>, VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    let mut leaves = Vec::new();
+    let mut visited = BTreeSet::new();
+    visited.insert(*root);
+    let mut stack = Vec::new();
+    stack.push((*nodes.get(root).ok_or(VerifyError::IncompleteProof)?, Vec::new(), 0));
+    while let Some((encoded_node, mut path, depth)) = stack.pop() {
+        if depth >= MAX_PROOF_DEPTH {
+            return Err(VerifyError::ProofTooDeep);
         }
-        Node::Branch(children, maybe_data) => {
-            process_branch::<L>(children, maybe_data, key, expected_value, proof)
+        let node = decode_at::<L>(encoded_node, depth)?;
+        let (children, value) = match node {
+            Node::Empty => continue,
+            Node::Leaf(nib, value) => {
+                path.extend(remaining_nibbles(&nib));
+                (Default::default(), Some(value))
+            }
+            Node::Extension(nib, handle) => {
+                path.extend(remaining_nibbles(&nib));
+                if let Some(child) = lookup_child::<L>(handle, nodes, &mut visited)? {
+                    stack.push((child, path, depth + 1));
+                }
+                continue;
+            }
+            Node::Branch(children, value) => (children, value),
+            Node::NibbledBranch(nib, children, value) => {
+                path.extend(remaining_nibbles(&nib));
+                (children, value)
+            }
+        };
+        let value = match value {
+            Some(Value::Inline(value)) => Some(value),
+            Some(Value::Node(plain_hash)) => decode_hash::<L::Hash>(plain_hash)
+                .ok_or_else(|| VerifyError::HashDecodeError(plain_hash.to_vec()))
+                .map(|hash| nodes.get(&hash).copied())?,
+            None => None,
+        };
+        if let Some(value) = value {
+            leaves.push((path.clone(), value.to_vec()));
         }
-        Node::NibbledBranch(nib, children, maybe_data) => {
-            process_nibbledbranch::<L>(nib, children, maybe_data, key, expected_value, proof)
+        // Push in reverse so children are visited in nibble order.
+        for (nibble, handle) in children.into_iter().enumerate().rev() {
+            let child = match handle {
+                Some(handle) => lookup_child::<L>(handle, nodes, &mut visited)?,
+                None => None,
+            };
+            if let Some(child) = child {
+                let mut child_path = path.clone();
+                child_path.push(nibble as u8);
+                stack.push((child, child_path, depth + 1));
+            }
         }
     }
+    Ok(leaves)
 }
 
-fn process_empty<'a, L>(
-    key: NibbleSlice<'a>,
-    expected_value: Option<&[u8]>,
-    _: &[Vec<u8>],
-) -> Result<(), VerifyError<'a, TrieHash<L>, CError<L>>>
+/// Resolve a child handle to its encoded node, or `None` if it is referenced by a hash that is not
+/// among `nodes`. The hash of a resolved node is added to `visited`, failing with
+/// [`VerifyError::NodeReachedTwice`] if it is already there.
+fn lookup_child<'a, L>(
+    handle: NodeHandle<'a>,
+    nodes: &BTreeMap<TrieHash<L>, &'a [u8]>,
+    visited: &mut BTreeSet<TrieHash<L>>,
+) -> Result<Option<&'a [u8]>, VerifyError<TrieHash<L>, CError<L>>>
 where
     L: TrieLayout,
 {
-    if expected_value.is_none() {
-        Ok(())
-    } else {
-        // println!("process_empty");
+    match handle {
+        NodeHandle::Inline(encoded_node) => Ok(Some(encoded_node)),
+        NodeHandle::Hash(plain_hash) => {
+            let hash = decode_hash::<L::Hash>(plain_hash)
+                .ok_or_else(|| VerifyError::HashDecodeError(plain_hash.to_vec()))?;
+            match nodes.get(&hash) {
+                Some(_) if !visited.insert(hash) => Err(VerifyError::NodeReachedTwice(hash)),
+                encoded_node => Ok(encoded_node.copied()),
+            }
+        }
+    }
+}
+
+/// Where a walk along a key ends: the value stored under the key, if any, the nibbles of the key
+/// left when the walk stopped, the number of them, counted from the point where the key stopped
+/// matching the path of the trie, the depth of the last node visited, and whether that node is a
+/// branch.
+pub(crate) struct PathEnd<'a> {
+    pub(crate) value: Option<&'a [u8]>,
+    pub(crate) key: NibbleSlice<'a>,
+    pub(crate) unmatched: usize,
+    pub(crate) depth: usize,
+    pub(crate) in_branch: bool,
+}
 
-        Err(VerifyError::NonExistingValue(key))
+impl<'a> PathEnd<'a> {
+    /// The value the walk ended at, failing with [`VerifyError::NonExistingValue`] if there is
+    /// none.
+    pub(crate) fn found<HO, CE>(&self) -> Result<&'a [u8], VerifyError<HO, CE>> {
+        self.value
+            .ok_or_else(|| VerifyError::NonExistingValue(remaining_nibbles(&self.key)))
+    }
+
+    /// Check the value the walk ended at against `expected_value`, which is `None` for an
+    /// exclusion proof.
+    pub(crate) fn check<HO, CE>(
+        &self,
+        expected_value: Option<&[u8]>,
+    ) -> Result<(), VerifyError<HO, CE>> {
+        match (self.value, expected_value) {
+            (None, None) => Ok(()),
+            (None, Some(_)) => Err(VerifyError::NonExistingValue(remaining_nibbles(&self.key))),
+            (Some(value), None) => Err(VerifyError::ExistingValue(value.to_vec())),
+            (Some(value), Some(expected)) if value_eq(value, expected) => Ok(()),
+            (Some(value), Some(_)) => Err(VerifyError::ValueMismatch(value.to_vec())),
+        }
     }
 }
 
-fn process_leaf<'a, L>(
-    nib: NibbleSlice,
-    data: Value<'a>,
+/// Walk a proof supplied in path order, checking every hash-referenced node against the reference
+/// held by its parent, and the root node against `root`. Inline children are decoded in place from
+/// their parent. Unlike [`process_node`], a node that does not hash to its reference is always
+/// reported as a [`VerifyError::HashMismatch`] at its depth.
+pub(crate) fn process_path_strict<'a, L>(
+    root: &TrieHash<L>,
+    proof: &'a [Vec<u8>],
     key: NibbleSlice<'a>,
     expected_value: Option<&[u8]>,
-    proof: &'a [Vec<u8>],
-) -> Result<(), VerifyError<'a, TrieHash<L>, CError<L>>>
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
 where
     L: TrieLayout,
 {
-    if key != nib && expected_value.is_none() {
-        return Ok(());
-    } else if key != nib {
-        return Err(VerifyError::NonExistingValue(key));
+    walk_path_strict::<L, _>(root, proof, key, None)?.check(expected_value)
+}
+
+/// Walk a proof along `key` as [`process_path_strict`] does, and return where the walk ends
+/// without checking the value found there. The hash of every proof node taken is pushed to
+/// `visited`, root first. Proof nodes may be owned or borrowed.
+pub(crate) fn walk_path_strict<'a, L, N>(
+    root: &TrieHash<L>,
+    proof: &'a [N],
+    mut key: NibbleSlice<'a>,
+    mut visited: Option<&mut Vec<TrieHash<L>>>,
+) -> Result<PathEnd<'a>, VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+    N: AsRef<[u8]>,
+{
+    let mut depth = 0;
+    let (mut encoded_node, mut proof) = take_checked::<L, N>(root, proof, depth)?;
+    if let Some(visited) = visited.as_mut() {
+        visited.push(*root);
+    }
+    loop {
+        if depth >= MAX_PROOF_DEPTH {
+            return Err(VerifyError::ProofTooDeep);
+        }
+        let node = decode_at::<L>(encoded_node, depth)?;
+        let in_branch = is_branch(&node);
+        let (handle, rest) = match step(node, key) {
+            ControlFlow::Continue(next) => next,
+            ControlFlow::Break((value, key, unmatched)) => {
+                let value = match value {
+                    None => None,
+                    Some(Value::Inline(value)) => Some(value),
+                    Some(Value::Node(plain_hash)) => {
+                        let hash = decode_hash::<L::Hash>(plain_hash)
+                            .ok_or_else(|| VerifyError::HashDecodeError(plain_hash.to_vec()))?;
+                        let (value, _) = take_checked::<L, N>(&hash, proof, depth + 1)?;
+                        if let Some(visited) = visited.as_mut() {
+                            visited.push(hash);
+                        }
+                        Some(value)
+                    }
+                };
+                return Ok(PathEnd {
+                    value,
+                    key,
+                    unmatched,
+                    depth,
+                    in_branch,
+                });
+            }
+        };
+        key = rest;
+        depth += 1;
+        match handle {
+            NodeHandle::Inline(inline_node) => encoded_node = inline_node,
+            NodeHandle::Hash(plain_hash) => {
+                let hash = decode_hash::<L::Hash>(plain_hash)
+                    .ok_or_else(|| VerifyError::HashDecodeError(plain_hash.to_vec()))?;
+                (encoded_node, proof) = take_checked::<L, N>(&hash, proof, depth)?;
+                if let Some(visited) = visited.as_mut() {
+                    visited.push(hash);
+                }
+            }
+        }
     }
-    match_value::<L>(Some(data), key, expected_value, proof)
 }
 
-fn process_extension<'a, L>(
-    nib: &NibbleSlice,
-    handle: NodeHandle<'a>,
+/// Walk `key` through nodes indexed by hash, as [`process_path_strict`] does through a path-ordered
+/// proof. Hash-referenced nodes are decoded once into `plans`, which is shared between the walks of
+/// several keys through the same nodes so that their common upper nodes are not decoded again.
+pub(crate) fn process_path_cached<'a, L>(
+    root: &TrieHash<L>,
+    nodes: &BTreeMap<TrieHash<L>, &'a [u8]>,
+    plans: &mut BTreeMap<TrieHash<L>, NodePlan>,
     mut key: NibbleSlice<'a>,
     expected_value: Option<&[u8]>,
-    proof: &'a [Vec<u8>],
-) -> Result<(), VerifyError<'a, TrieHash<L>, CError<L>>>
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
 where
     L: TrieLayout,
 {
-    if !key.starts_with(nib) && expected_value.is_none() {
-        return Ok(());
-    } else if !key.starts_with(nib) {
-        return Err(VerifyError::NonExistingValue(key));
+    let mut depth = 0;
+    let mut node = cached_node::<L>(root, nodes, plans, depth)?;
+    loop {
+        if depth >= MAX_PROOF_DEPTH {
+            return Err(VerifyError::ProofTooDeep);
+        }
+        let in_branch = is_branch(&node);
+        let (handle, rest) = match step(node, key) {
+            ControlFlow::Continue(next) => next,
+            ControlFlow::Break((value, key, unmatched)) => {
+                let value = match value {
+                    None => None,
+                    Some(Value::Inline(value)) => Some(value),
+                    Some(Value::Node(plain_hash)) => {
+                        let hash = decode_hash::<L::Hash>(plain_hash)
+                            .ok_or_else(|| VerifyError::HashDecodeError(plain_hash.to_vec()))?;
+                        Some(*nodes.get(&hash).ok_or(VerifyError::IncompleteProof)?)
+                    }
+                };
+                return PathEnd {
+                    value,
+                    key,
+                    unmatched,
+                    depth,
+                    in_branch,
+                }
+                .check(expected_value);
+            }
+        };
+        key = rest;
+        depth += 1;
+        node = match handle {
+            NodeHandle::Inline(inline_node) => decode_at::<L>(inline_node, depth)?,
+            NodeHandle::Hash(plain_hash) => {
+                let hash = decode_hash::<L::Hash>(plain_hash)
+                    .ok_or_else(|| VerifyError::HashDecodeError(plain_hash.to_vec()))?;
+                cached_node::<L>(&hash, nodes, plans, depth)?
+            }
+        };
     }
-    key.advance(nib.len());
+}
 
+/// The node indexed under `hash`, decoded from its plan in `plans`, which is decoded and stored
+/// there the first time the node is reached.
+fn cached_node<'a, L>(
+    hash: &TrieHash<L>,
+    nodes: &BTreeMap<TrieHash<L>, &'a [u8]>,
+    plans: &mut BTreeMap<TrieHash<L>, NodePlan>,
+    depth: usize,
+) -> Result<Node<'a>, VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    let encoded_node = *nodes.get(hash).ok_or(VerifyError::IncompleteProof)?;
+    if !plans.contains_key(hash) {
+        let plan = <L::Codec as NodeCodec>::decode_plan(encoded_node)
+            .map_err(|source| VerifyError::DecodeError { depth, source })?;
+        plans.insert(*hash, plan);
+    }
+    Ok(plans[hash].build(encoded_node))
+}
+
+/// One step of an iterative walk: continue with the child the key leads to and the rest of the key,
+/// or, if the walk ends, break with the value stored under the key, the nibbles of the key left and
+/// the number of nibbles of the key left from the point where it stopped matching the path.
+type Step<'a> =
+    ControlFlow<(Option<Value<'a>>, NibbleSlice<'a>, usize), (NodeHandle<'a>, NibbleSlice<'a>)>;
+
+fn is_branch(node: &Node) -> bool {
+    matches!(node, Node::Branch(..) | Node::NibbledBranch(..))
+}
+
+/// Advance `key` through `node` in an iterative walk.
+fn step<'a>(node: Node<'a>, mut key: NibbleSlice<'a>) -> Step<'a> {
+    let left_from_divergence =
+        |key: &NibbleSlice, nib: &NibbleSlice| key.len() - key.common_prefix(nib);
+    let (handle, before_child) = match node {
+        Node::Empty => (None, key.len()),
+        Node::Leaf(nib, value) => {
+            let unmatched = left_from_divergence(&key, &nib);
+            return ControlFlow::Break(((key == nib).then_some(value), key, unmatched));
+        }
+        Node::Extension(nib, handle) => {
+            if !key.starts_with(&nib) {
+                let unmatched = left_from_divergence(&key, &nib);
+                return ControlFlow::Break((None, key, unmatched));
+            }
+            key.advance(nib.len());
+            (Some(handle), key.len())
+        }
+        Node::Branch(mut children, value) => {
+            if key.is_empty() {
+                return ControlFlow::Break((value, key, 0));
+            }
+            let before_child = key.len();
+            let handle = children[key.at(0) as usize].take();
+            key.advance(1);
+            (handle, before_child)
+        }
+        Node::NibbledBranch(nib, mut children, value) => {
+            if !key.starts_with(&nib) {
+                let unmatched = left_from_divergence(&key, &nib);
+                return ControlFlow::Break((None, key, unmatched));
+            }
+            key.advance(nib.len());
+            if key.is_empty() {
+                return ControlFlow::Break((value, key, 0));
+            }
+            let before_child = key.len();
+            let handle = children[key.at(0) as usize].take();
+            key.advance(1);
+            (handle, before_child)
+        }
+    };
     match handle {
-        NodeHandle::Inline(encoded_node) => {
-            process_node::<L>(None, encoded_node, key, expected_value, proof)
+        Some(handle) => ControlFlow::Continue((handle, key)),
+        None => ControlFlow::Break((None, key, before_child)),
+    }
+}
+
+/// Take the next node of a path-ordered proof and check that it hashes to `hash`.
+fn take_checked<'a, L, N>(
+    hash: &TrieHash<L>,
+    proof: &'a [N],
+    depth: usize,
+) -> Result<(&'a [u8], &'a [N]), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+    N: AsRef<[u8]>,
+{
+    let (encoded_node, rest) = proof.split_first().ok_or(VerifyError::IncompleteProof)?;
+    let encoded_node = encoded_node.as_ref();
+    let calculated_node_hash = <L::Hash as Hasher>::hash(encoded_node);
+    if calculated_node_hash != *hash {
+        return Err(VerifyError::HashMismatch {
+            hash: calculated_node_hash,
+            depth,
+        });
+    }
+    Ok((encoded_node, rest))
+}
+
+/// Walk a path-ordered proof from `root` and reassemble the nibbles of the path it leads to from
+/// the nodes themselves: the partials of extensions and of the leaf, and the index of the child
+/// taken at each branch. `key` only chooses that child, so a proof whose partials do not match
+/// `key` still yields a path, which differs from it. Every node taken is checked against its hash.
+pub(crate) fn reassemble_path<L>(
+    root: &TrieHash<L>,
+    proof: &[Vec<u8>],
+    key: NibbleSlice,
+) -> Result<Vec<u8>, VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    let mut path = Vec::new();
+    let mut depth = 0;
+    let (mut encoded_node, mut proof) = take_checked::<L, _>(root, proof, depth)?;
+    loop {
+        if depth >= MAX_PROOF_DEPTH {
+            return Err(VerifyError::ProofTooDeep);
         }
-        NodeHandle::Hash(plain_hash) => {
-            let new_root = decode_hash::<L::Hash>(plain_hash)
-                .ok_or(VerifyError::HashDecodeError(plain_hash))?;
-            process_node::<L>(Some(&new_root), &proof[0], key, expected_value, &proof[1..])
+        let handle = match decode_at::<L>(encoded_node, depth)? {
+            Node::Empty => return Err(VerifyError::NonExistingValue(key_from(&key, path.len()))),
+            Node::Leaf(nib, _) => {
+                path.extend((0..nib.len()).map(|i| nib.at(i)));
+                return Ok(path);
+            }
+            Node::Extension(nib, handle) => {
+                path.extend((0..nib.len()).map(|i| nib.at(i)));
+                handle
+            }
+            Node::Branch(children, value) => match branch_child(children, value, &key, &mut path)? {
+                Some(handle) => handle,
+                None => return Ok(path),
+            },
+            Node::NibbledBranch(nib, children, value) => {
+                path.extend((0..nib.len()).map(|i| nib.at(i)));
+                match branch_child(children, value, &key, &mut path)? {
+                    Some(handle) => handle,
+                    None => return Ok(path),
+                }
+            }
+        };
+        depth += 1;
+        match handle {
+            NodeHandle::Inline(inline_node) => encoded_node = inline_node,
+            NodeHandle::Hash(plain_hash) => {
+                let hash = decode_hash::<L::Hash>(plain_hash)
+                    .ok_or_else(|| VerifyError::HashDecodeError(plain_hash.to_vec()))?;
+                (encoded_node, proof) = take_checked::<L, _>(&hash, proof, depth)?;
+            }
         }
     }
 }
 
-fn process_nibbledbranch<'a, L>(
-    nib: NibbleSlice,
-    children: [Option<NodeHandle<'a>>; 16],
-    maybe_data: Option<Value<'a>>,
+/// The child of a branch taken by [`reassemble_path`]: the one under the nibble of `key` at the
+/// end of `path`, which is pushed to `path`, or `None` if the path already covers the key and the
+/// branch holds a value.
+fn branch_child<'a, HO, CE>(
+    mut children: [Option<NodeHandle<'a>>; 16],
+    value: Option<Value>,
+    key: &NibbleSlice,
+    path: &mut Vec<u8>,
+) -> Result<Option<NodeHandle<'a>>, VerifyError<HO, CE>> {
+    if path.len() >= key.len() {
+        return match value {
+            Some(_) => Ok(None),
+            None => Err(VerifyError::NonExistingValue(Vec::new())),
+        };
+    }
+    let index = key.at(path.len());
+    path.push(index);
+    match children[index as usize].take() {
+        Some(handle) => Ok(Some(handle)),
+        None => Err(VerifyError::NonExistingValue(key_from(key, path.len()))),
+    }
+}
+
+/// The nibbles of `key` from `start` on.
+fn key_from(key: &NibbleSlice, start: usize) -> Vec<u8> {
+    (start..key.len()).map(|i| key.at(i)).collect()
+}
+
+/// The codec of the nodes [`encode_compact_node`] re-encodes, which may omit children.
+type CompactCodec<L> = node_codec::TrieDbProofCodec<<L as TrieLayout>::Hash>;
+
+/// Re-encode `encoded`, a node on the path of `key`, in the format of `trie_db::proof`: references
+/// to the hash-referenced children that follow in `proof` are omitted, as is the value stored under
+/// `key`, and everything else is kept. Those children are re-encoded in turn and appended to `out`
+/// in path order. Returns the re-encoded node, which an inline parent embeds as is, and the proof
+/// nodes left.
+pub(crate) fn encode_compact_node<'a, L>(
+    encoded: &'a [u8],
     mut key: NibbleSlice<'a>,
-    expected_value: Option<&[u8]>,
     proof: &'a [Vec<u8>],
-) -> Result<(), VerifyError<'a, TrieHash<L>, CError<L>>>
+    depth: usize,
+    out: &mut Vec<Vec<u8>>,
+) -> Result<(Vec<u8>, &'a [Vec<u8>]), VerifyError<TrieHash<L>, CError<L>>>
 where
     L: TrieLayout,
 {
-    if !key.starts_with(&nib) && expected_value.is_none() {
-        return Ok(());
-    } else if !key.starts_with(&nib) && expected_value.is_some() {
-        // println!("process_nibbledbranch");
-        return Err(VerifyError::NonExistingValue(key));
+    if depth >= MAX_PROOF_DEPTH {
+        return Err(VerifyError::ProofTooDeep);
     }
-    key.advance(nib.len());
-
-    if key.is_empty() {
-        match_value::<L>(maybe_data, key, expected_value, proof)
-    } else {
-        match_children::<L>(children, key, expected_value, proof)
+    let node = decode_at::<L>(encoded, depth)?;
+    let unchanged = || Ok((encoded.to_vec(), proof));
+    match node {
+        Node::Leaf(partial, _) if partial == key => {
+            let leaf = CompactCodec::<L>::leaf_node(
+                partial.right_iter(),
+                partial.len(),
+                Value::Inline(&[]),
+            );
+            Ok((leaf, proof))
+        }
+        Node::Extension(partial, child) if key.starts_with(&partial) => {
+            key.advance(partial.len());
+            let (child, proof) = encode_compact_child::<L>(child, key, proof, depth, out)?;
+            let extension =
+                CompactCodec::<L>::extension_node(partial.right_iter(), partial.len(), child);
+            Ok((extension, proof))
+        }
+        Node::Branch(mut children, value) => {
+            let mut references = Vec::with_capacity(children.len());
+            for child in children.iter() {
+                references.push(child.as_ref().map(child_reference::<L>).transpose()?);
+            }
+            if key.is_empty() {
+                if value.is_none() {
+                    return unchanged();
+                }
+                let branch = CompactCodec::<L>::branch_node(references.iter(), None);
+                return Ok((branch, proof));
+            }
+            let index = key.at(0) as usize;
+            key.advance(1);
+            match children[index].take() {
+                Some(child) => {
+                    let (child, proof) = encode_compact_child::<L>(child, key, proof, depth, out)?;
+                    references[index] = Some(child);
+                    let branch = CompactCodec::<L>::branch_node(references.iter(), value);
+                    Ok((branch, proof))
+                }
+                None => unchanged(),
+            }
+        }
+        _ => unchanged(),
     }
 }
 
-fn process_branch<'a, L>(
-    children: [Option<NodeHandle<'a>>; 16],
-    maybe_data: Option<Value<'a>>,
+/// Re-encode a child on the path as [`encode_compact_node`] does, and return the reference its
+/// parent should hold: omitted for a hash-referenced child, which is appended to `out`, or the
+/// re-encoded child for an inline one.
+fn encode_compact_child<'a, L>(
+    child: NodeHandle<'a>,
     key: NibbleSlice<'a>,
-    expected_value: Option<&[u8]>,
     proof: &'a [Vec<u8>],
-) -> Result<(), VerifyError<'a, TrieHash<L>, CError<L>>>
+    depth: usize,
+    out: &mut Vec<Vec<u8>>,
+) -> Result<(ChildReference<TrieHash<L>>, &'a [Vec<u8>]), VerifyError<TrieHash<L>, CError<L>>>
 where
     L: TrieLayout,
 {
-    if key.is_empty() {
-        match_value::<L>(maybe_data, key, expected_value, proof)
-    } else {
-        match_children::<L>(children, key, expected_value, proof)
+    match child {
+        NodeHandle::Hash(plain_hash) => {
+            let hash = decode_hash::<L::Hash>(plain_hash)
+                .ok_or_else(|| VerifyError::HashDecodeError(plain_hash.to_vec()))?;
+            let (encoded, proof) = take_checked::<L, _>(&hash, proof, depth + 1)?;
+            let index = out.len();
+            out.push(Vec::new());
+            let (node, proof) = encode_compact_node::<L>(encoded, key, proof, depth + 1, out)?;
+            out[index] = node;
+            Ok((ChildReference::Inline(TrieHash::<L>::default(), 0), proof))
+        }
+        NodeHandle::Inline(encoded) => {
+            let (node, proof) = encode_compact_node::<L>(encoded, key, proof, depth + 1, out)?;
+            Ok((child_reference::<L>(&NodeHandle::Inline(&node))?, proof))
+        }
     }
 }
-fn match_children<'a, L>(
-    children: [Option<NodeHandle<'a>>; 16],
+
+/// The reference a parent holds to `child`: its hash, or the child itself if it is inline.
+fn child_reference<L>(
+    child: &NodeHandle,
+) -> Result<ChildReference<TrieHash<L>>, VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    match child {
+        NodeHandle::Hash(plain_hash) => decode_hash::<L::Hash>(plain_hash)
+            .map(ChildReference::Hash)
+            .ok_or_else(|| VerifyError::HashDecodeError(plain_hash.to_vec())),
+        NodeHandle::Inline(encoded) => {
+            let mut inline = TrieHash::<L>::default();
+            if encoded.len() > inline.as_ref().len() {
+                return Err(VerifyError::HashDecodeError(encoded.to_vec()));
+            }
+            inline.as_mut()[..encoded.len()].copy_from_slice(encoded);
+            Ok(ChildReference::Inline(inline, encoded.len()))
+        }
+    }
+}
+
+/// Source of the nodes referenced by hash while walking a proof.
+pub(crate) trait ProofNodes<'a, H: Hasher>: Copy {
+    /// Take the node that should hash to `hash`, along with the nodes left for the rest of the
+    /// walk.
+    fn take(self, hash: &H::Out) -> Option<(&'a [u8], Self)>;
+
+    /// Whether any of the remaining nodes hashes to `hash`.
+    fn contains(self, hash: &H::Out) -> bool;
+}
+
+/// Nodes in path order: each lookup consumes the next node, whose hash is checked by the caller.
+impl<'a, H: Hasher> ProofNodes<'a, H> for &'a [Vec<u8>] {
+    fn take(self, _hash: &H::Out) -> Option<(&'a [u8], Self)> {
+        self.split_first().map(|(node, rest)| (node.as_slice(), rest))
+    }
+
+    fn contains(self, hash: &H::Out) -> bool {
+        self.iter().any(|node| H::hash(node) == *hash)
+    }
+}
+
+/// Nodes indexed by hash: lookups are independent of the order the nodes were supplied in.
+impl<'a, 'b, H: Hasher> ProofNodes<'a, H> for &'b BTreeMap<H::Out, &'a [u8]> {
+    fn take(self, hash: &H::Out) -> Option<(&'a [u8], Self)> {
+        self.get(hash).map(|node| (*node, self))
+    }
+
+    fn contains(self, hash: &H::Out) -> bool {
+        self.contains_key(hash)
+    }
+}
+
+/// The kind of a node, as recorded in `tracing` spans.
+#[cfg(feature = "tracing")]
+fn node_kind(node: &Node) -> &'static str {
+    match node {
+        Node::Empty => "empty",
+        Node::Leaf(..) => "leaf",
+        Node::Extension(..) => "extension",
+        Node::Branch(..) => "branch",
+        Node::NibbledBranch(..) => "nibbled_branch",
+    }
+}
+
+/// Walk a proof from `encoded_node` along `key`, checking every hash-referenced node against the
+/// reference held by its parent, or against `expected_node_hash` for the first one, and the value
+/// under the key against `expected_value`. The walk is a loop over the current node, its expected
+/// hash and the key left, so deep paths do not grow the stack.
+pub(crate) fn process_node<'a, L, P>(
+    expected_node_hash: Option<&<L::Hash as Hasher>::Out>,
+    encoded_node: &'a [u8],
+    key: NibbleSlice<'a>,
+    expected_value: Option<&[u8]>,
+    proof: P,
+    depth: usize,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+    P: ProofNodes<'a, L::Hash>,
+{
+    process_node_with::<L, P>(
+        expected_node_hash,
+        encoded_node,
+        key,
+        expected_value,
+        proof,
+        depth,
+        &VerifyOptions::default(),
+    )
+}
+
+/// Walk a proof as [`process_node`] does, with the depth limit and value comparison of `options`.
+pub(crate) fn process_node_with<'a, L, P>(
+    expected_node_hash: Option<&<L::Hash as Hasher>::Out>,
+    mut encoded_node: &'a [u8],
     mut key: NibbleSlice<'a>,
     expected_value: Option<&[u8]>,
-    proof: &'a [Vec<u8>],
-) -> Result<(), VerifyError<'a, TrieHash<L>, CError<L>>>
+    mut proof: P,
+    mut depth: usize,
+    options: &VerifyOptions,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
 where
     L: TrieLayout,
+    P: ProofNodes<'a, L::Hash>,
 {
-    match children.get(key.at(0) as usize) {
-        Some(Some(NodeHandle::Hash(hash))) => {
-            if proof.is_empty() {
-                Err(VerifyError::IncompleteProof)
-            } else {
-                key.advance(1);
-                let new_root =
-                    decode_hash::<L::Hash>(hash).ok_or(VerifyError::HashDecodeError(hash))?;
-                process_node::<L>(Some(&new_root), &proof[0], key, expected_value, &proof[1..])
-            }
+    let constant_time = options.constant_time;
+    let mut expected_node_hash = expected_node_hash.copied();
+    loop {
+        if depth >= options.max_depth {
+            return Err(VerifyError::ProofTooDeep);
         }
-        Some(Some(NodeHandle::Inline(encoded_node))) => {
-            key.advance(1);
-            process_node::<L>(None, encoded_node, key, expected_value, proof)
+        // The node is authenticated before anything in it is trusted, including the root node,
+        // whose partial path is only consumed once it hashes to the root. A value is only ever
+        // read from the value slot of a leaf or branch, never from a whole node.
+        if let Some(expected) = expected_node_hash {
+            let calculated_node_hash = <L::Hash as Hasher>::hash(encoded_node);
+            if calculated_node_hash != expected {
+                if proof.contains(&expected) {
+                    return Err(VerifyError::NodesOutOfOrder);
+                }
+                if depth == 0 {
+                    return Err(VerifyError::RootMismatch {
+                        expected,
+                        found: calculated_node_hash,
+                    });
+                }
+                return Err(VerifyError::HashMismatch {
+                    hash: calculated_node_hash,
+                    depth,
+                });
+            }
         }
-        Some(None) => {
-            if expected_value.is_none() {
-                Ok(())
-            } else {
-                Err(VerifyError::NonExistingValue(key))
+        let node = decode_at::<L>(encoded_node, depth)?;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("process_node", node = node_kind(&node), depth).entered();
+        let handle = match node {
+            Node::Empty => return absent(&key, expected_value),
+            Node::Leaf(nib, data) => {
+                if key != nib {
+                    return absent(&key, expected_value);
+                }
+                return match_value::<L, P>(
+                    Some(data),
+                    key,
+                    expected_value,
+                    proof,
+                    depth,
+                    constant_time,
+                );
+            }
+            Node::Extension(nib, handle) => {
+                if !key.starts_with(&nib) {
+                    return absent(&key, expected_value);
+                }
+                key.advance(nib.len());
+                handle
+            }
+            Node::Branch(children, maybe_data) => {
+                if key.is_empty() {
+                    return match_value::<L, P>(
+                        maybe_data,
+                        key,
+                        expected_value,
+                        proof,
+                        depth,
+                        constant_time,
+                    );
+                }
+                match child(children, &mut key) {
+                    Some(handle) => handle,
+                    None => return absent(&key, expected_value),
+                }
+            }
+            Node::NibbledBranch(nib, children, maybe_data) => {
+                if !key.starts_with(&nib) {
+                    return absent(&key, expected_value);
+                }
+                key.advance(nib.len());
+                if key.is_empty() {
+                    return match_value::<L, P>(
+                        maybe_data,
+                        key,
+                        expected_value,
+                        proof,
+                        depth,
+                        constant_time,
+                    );
+                }
+                match child(children, &mut key) {
+                    Some(handle) => handle,
+                    None => return absent(&key, expected_value),
+                }
+            }
+        };
+
+        depth += 1;
+        match handle {
+            NodeHandle::Inline(inline_node) => {
+                expected_node_hash = None;
+                encoded_node = inline_node;
+            }
+            NodeHandle::Hash(plain_hash) => {
+                let hash = decode_hash::<L::Hash>(plain_hash)
+                    .ok_or_else(|| VerifyError::HashDecodeError(plain_hash.to_vec()))?;
+                (encoded_node, proof) = proof.take(&hash).ok_or(VerifyError::IncompleteProof)?;
+                expected_node_hash = Some(hash);
             }
         }
-        None => panic!("key index is out of range in children array"),
     }
 }
 
-fn match_value<'a, L>(
+/// The outcome of a walk that ends without a value under the key: an exclusion proof when no value
+/// is expected.
+fn absent<HO, CE>(
+    key: &NibbleSlice,
+    expected_value: Option<&[u8]>,
+) -> Result<(), VerifyError<HO, CE>> {
+    match expected_value {
+        None => Ok(()),
+        Some(_) => Err(VerifyError::NonExistingValue(remaining_nibbles(key))),
+    }
+}
+
+/// The child of a branch that `key` leads to, advancing the key past its index nibble. The key is
+/// left as is if there is no such child.
+fn child<'a>(
+    mut children: [Option<NodeHandle<'a>>; 16],
+    key: &mut NibbleSlice<'a>,
+) -> Option<NodeHandle<'a>> {
+    let handle = children[key.at(0) as usize].take()?;
+    key.advance(1);
+    Some(handle)
+}
+
+fn match_value<'a, L, P>(
     maybe_data: Option<Value<'a>>,
     key: NibbleSlice<'a>,
     expected_value: Option<&[u8]>,
-    proof: &'a [Vec<u8>],
-) -> Result<(), VerifyError<'a, TrieHash<L>, CError<L>>>
+    proof: P,
+    depth: usize,
+    constant_time: bool,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
 where
     L: TrieLayout,
+    P: ProofNodes<'a, L::Hash>,
 {
-    println!("match_value");
-    match (maybe_data, proof.first(), expected_value) {
+    let next_proof_item = match maybe_data {
+        Some(Value::Node(plain_hash)) => decode_hash::<L::Hash>(plain_hash)
+            .and_then(|hash| proof.take(&hash))
+            .map(|(node, _)| node),
+        _ => None,
+    };
+    match (maybe_data, next_proof_item, expected_value) {
         (None, _, None) => Ok(()),
-        (None, _, Some(_)) => Err(VerifyError::NonExistingValue(key)),
+        (None, _, Some(_)) => Err(VerifyError::NonExistingValue(remaining_nibbles(&key))),
         (Some(Value::Inline(inline_data)), _, Some(value)) => {
-            if inline_data == value {
+            if values_eq(inline_data, value, constant_time) {
                 Ok(())
             } else {
                 Err(VerifyError::ValueMismatch(inline_data.to_vec()))
@@ -288,10 +1142,13 @@ where
         (Some(Value::Node(plain_hash)), Some(next_proof_item), Some(value)) => {
             let value_hash = L::Hash::hash(value);
             let node_hash = decode_hash::<L::Hash>(plain_hash)
-                .ok_or(VerifyError::HashDecodeError(plain_hash))?;
+                .ok_or_else(|| VerifyError::HashDecodeError(plain_hash.to_vec()))?;
             if node_hash != value_hash {
-                Err(VerifyError::HashMismatch(node_hash))
-            } else if next_proof_item != value {
+                Err(VerifyError::HashMismatch {
+                    hash: node_hash,
+                    depth,
+                })
+            } else if !values_eq(next_proof_item, value, constant_time) {
                 Err(VerifyError::ValueMismatch(next_proof_item.to_vec()))
             } else {
                 Ok(())
@@ -303,3 +1160,245 @@ where
         }
     }
 }
+
+/// The recursive walk [`process_node`] replaced, kept as a baseline for its tests.
+#[cfg(test)]
+pub(crate) mod recursive {
+    use super::*;
+
+    pub(crate) fn process_node<'a, L, P>(
+        expected_node_hash: Option<&<L::Hash as Hasher>::Out>,
+        encoded_node: &'a [u8],
+        key: NibbleSlice<'a>,
+        expected_value: Option<&[u8]>,
+        proof: P,
+        depth: usize,
+    ) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+    where
+        L: TrieLayout,
+        P: ProofNodes<'a, L::Hash>,
+    {
+        if depth >= MAX_PROOF_DEPTH {
+            return Err(VerifyError::ProofTooDeep);
+        }
+        if let Some(expected) = expected_node_hash {
+            let calculated_node_hash = <L::Hash as Hasher>::hash(encoded_node);
+            if calculated_node_hash != *expected {
+                if proof.contains(expected) {
+                    return Err(VerifyError::NodesOutOfOrder);
+                }
+                if depth == 0 {
+                    return Err(VerifyError::RootMismatch {
+                        expected: *expected,
+                        found: calculated_node_hash,
+                    });
+                }
+                return Err(VerifyError::HashMismatch {
+                    hash: calculated_node_hash,
+                    depth,
+                });
+            }
+        }
+        let node = decode_at::<L>(encoded_node, depth)?;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("process_node", node = node_kind(&node), depth).entered();
+        match node {
+            Node::Empty => process_empty::<L, P>(key, expected_value, proof),
+            Node::Leaf(nib, data) => {
+                process_leaf::<L, P>(nib, data, key, expected_value, proof, depth)
+            }
+            Node::Extension(nib, handle) => {
+                process_extension::<L, P>(&nib, handle, key, expected_value, proof, depth)
+            }
+            Node::Branch(children, maybe_data) => {
+                process_branch::<L, P>(children, maybe_data, key, expected_value, proof, depth)
+            }
+            Node::NibbledBranch(nib, children, maybe_data) => process_nibbledbranch::<L, P>(
+                nib,
+                children,
+                maybe_data,
+                key,
+                expected_value,
+                proof,
+                depth,
+            ),
+        }
+    }
+
+    fn process_empty<'a, L, P>(
+        key: NibbleSlice<'a>,
+        expected_value: Option<&[u8]>,
+        _: P,
+    ) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+    where
+        L: TrieLayout,
+        P: ProofNodes<'a, L::Hash>,
+    {
+        if expected_value.is_none() {
+            Ok(())
+        } else {
+            Err(VerifyError::NonExistingValue(remaining_nibbles(&key)))
+        }
+    }
+
+    fn process_leaf<'a, L, P>(
+        nib: NibbleSlice,
+        data: Value<'a>,
+        key: NibbleSlice<'a>,
+        expected_value: Option<&[u8]>,
+        proof: P,
+        depth: usize,
+    ) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+    where
+        L: TrieLayout,
+        P: ProofNodes<'a, L::Hash>,
+    {
+        if key != nib && expected_value.is_none() {
+            return Ok(());
+        } else if key != nib {
+            return Err(VerifyError::NonExistingValue(remaining_nibbles(&key)));
+        }
+        match_value::<L, P>(Some(data), key, expected_value, proof, depth, DEFAULT_CONSTANT_TIME)
+    }
+
+    fn process_extension<'a, L, P>(
+        nib: &NibbleSlice,
+        handle: NodeHandle<'a>,
+        mut key: NibbleSlice<'a>,
+        expected_value: Option<&[u8]>,
+        proof: P,
+        depth: usize,
+    ) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+    where
+        L: TrieLayout,
+        P: ProofNodes<'a, L::Hash>,
+    {
+        if !key.starts_with(nib) && expected_value.is_none() {
+            return Ok(());
+        } else if !key.starts_with(nib) {
+            return Err(VerifyError::NonExistingValue(remaining_nibbles(&key)));
+        }
+        key.advance(nib.len());
+
+        match handle {
+            NodeHandle::Inline(encoded_node) => {
+                process_node::<L, P>(None, encoded_node, key, expected_value, proof, depth + 1)
+            }
+            NodeHandle::Hash(plain_hash) => {
+                let new_root = decode_hash::<L::Hash>(plain_hash)
+                    .ok_or_else(|| VerifyError::HashDecodeError(plain_hash.to_vec()))?;
+                let (encoded_node, proof) =
+                    proof.take(&new_root).ok_or(VerifyError::IncompleteProof)?;
+                process_node::<L, P>(
+                    Some(&new_root),
+                    encoded_node,
+                    key,
+                    expected_value,
+                    proof,
+                    depth + 1,
+                )
+            }
+        }
+    }
+
+    fn process_nibbledbranch<'a, L, P>(
+        nib: NibbleSlice,
+        children: [Option<NodeHandle<'a>>; 16],
+        maybe_data: Option<Value<'a>>,
+        mut key: NibbleSlice<'a>,
+        expected_value: Option<&[u8]>,
+        proof: P,
+        depth: usize,
+    ) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+    where
+        L: TrieLayout,
+        P: ProofNodes<'a, L::Hash>,
+    {
+        if !key.starts_with(&nib) && expected_value.is_none() {
+            return Ok(());
+        } else if !key.starts_with(&nib) && expected_value.is_some() {
+            return Err(VerifyError::NonExistingValue(remaining_nibbles(&key)));
+        }
+        key.advance(nib.len());
+
+        if key.is_empty() {
+            match_value::<L, P>(
+                maybe_data,
+                key,
+                expected_value,
+                proof,
+                depth,
+                DEFAULT_CONSTANT_TIME,
+            )
+        } else {
+            match_children::<L, P>(children, key, expected_value, proof, depth)
+        }
+    }
+
+    fn process_branch<'a, L, P>(
+        children: [Option<NodeHandle<'a>>; 16],
+        maybe_data: Option<Value<'a>>,
+        key: NibbleSlice<'a>,
+        expected_value: Option<&[u8]>,
+        proof: P,
+        depth: usize,
+    ) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+    where
+        L: TrieLayout,
+        P: ProofNodes<'a, L::Hash>,
+    {
+        if key.is_empty() {
+            match_value::<L, P>(
+                maybe_data,
+                key,
+                expected_value,
+                proof,
+                depth,
+                DEFAULT_CONSTANT_TIME,
+            )
+        } else {
+            match_children::<L, P>(children, key, expected_value, proof, depth)
+        }
+    }
+    fn match_children<'a, L, P>(
+        children: [Option<NodeHandle<'a>>; 16],
+        mut key: NibbleSlice<'a>,
+        expected_value: Option<&[u8]>,
+        proof: P,
+        depth: usize,
+    ) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+    where
+        L: TrieLayout,
+        P: ProofNodes<'a, L::Hash>,
+    {
+        match children.get(key.at(0) as usize) {
+            Some(Some(NodeHandle::Hash(hash))) => {
+                key.advance(1);
+                let new_root = decode_hash::<L::Hash>(hash)
+                    .ok_or_else(|| VerifyError::HashDecodeError(hash.to_vec()))?;
+                let (encoded_node, proof) =
+                    proof.take(&new_root).ok_or(VerifyError::IncompleteProof)?;
+                process_node::<L, P>(
+                    Some(&new_root),
+                    encoded_node,
+                    key,
+                    expected_value,
+                    proof,
+                    depth + 1,
+                )
+            }
+            Some(Some(NodeHandle::Inline(encoded_node))) => {
+                key.advance(1);
+                process_node::<L, P>(None, encoded_node, key, expected_value, proof, depth + 1)
+            }
+            Some(None) => {
+                if expected_value.is_none() {
+                    Ok(())
+                } else {
+                    Err(VerifyError::NonExistingValue(remaining_nibbles(&key)))
+                }
+            }
+            None => panic!("key index is out of range in children array"),
+        }
+    }
+}
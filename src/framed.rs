@@ -0,0 +1,173 @@
+//! A self-describing binary framing of a proof with the root it verifies against, for storing
+//! proofs on disk or sending them on the wire without tracking the root separately. A framed proof
+//! is a magic byte, a version byte, the 32-byte root, the number of nodes as a 4-byte little-endian
+//! integer, and each node prefixed with its length as a 4-byte little-endian integer.
+
+use crate::rstd::vec::Vec;
+use crate::types::B256;
+
+/// The first byte of every framed proof.
+pub const MAGIC: u8 = 0xe7;
+/// The version of the framing written by [`encode_framed_proof`].
+pub const VERSION: u8 = 1;
+
+const HEADER_LEN: usize = 2 + 32 + LEN_LEN;
+const LEN_LEN: usize = 4;
+
+/// Failure to decode a framed proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The first byte is not [`MAGIC`].
+    BadMagic(u8),
+    /// The version is not one this crate can decode.
+    UnsupportedVersion(u8),
+    /// The bytes end before the header or a node does.
+    UnexpectedEnd { len: usize, expected: usize },
+    /// There are bytes left after the last node.
+    TrailingBytes(usize),
+}
+
+/// Frame `proof` along with the `root` it verifies against.
+///
+/// Panics if there are more than `u32::MAX` nodes, or a node is longer than `u32::MAX` bytes.
+pub fn encode_framed_proof(root: B256, proof: &[Vec<u8>]) -> Vec<u8> {
+    let len = HEADER_LEN + proof.iter().map(|node| LEN_LEN + node.len()).sum::<usize>();
+    let mut bytes = Vec::with_capacity(len);
+    bytes.push(MAGIC);
+    bytes.push(VERSION);
+    bytes.extend_from_slice(root.as_slice());
+    bytes.extend_from_slice(&encode_len(proof.len()));
+    for node in proof.iter() {
+        bytes.extend_from_slice(&encode_len(node.len()));
+        bytes.extend_from_slice(node);
+    }
+    bytes
+}
+
+/// Decode a proof and its root framed by [`encode_framed_proof`]. The nodes are not decoded.
+pub fn decode_framed_proof(bytes: &[u8]) -> Result<(B256, Vec<Vec<u8>>), DecodeError> {
+    let header = bytes.get(..HEADER_LEN).ok_or(DecodeError::UnexpectedEnd {
+        len: bytes.len(),
+        expected: HEADER_LEN,
+    })?;
+    if header[0] != MAGIC {
+        return Err(DecodeError::BadMagic(header[0]));
+    }
+    if header[1] != VERSION {
+        return Err(DecodeError::UnsupportedVersion(header[1]));
+    }
+    let root = B256::from_slice(&header[2..34]);
+    let count = read_len(bytes, 34)?;
+
+    // Every node takes at least its length prefix, which bounds the allocation by the input.
+    let mut proof = Vec::with_capacity(count.min((bytes.len() - HEADER_LEN) / LEN_LEN));
+    let mut at = HEADER_LEN;
+    for _ in 0..count {
+        let len = read_len(bytes, at)?;
+        at += LEN_LEN;
+        // The length is untrusted, and the sum can overflow where `usize` is 32 bits.
+        let end = at.checked_add(len).ok_or(DecodeError::UnexpectedEnd {
+            len: bytes.len(),
+            expected: usize::MAX,
+        })?;
+        let node = bytes.get(at..end).ok_or(DecodeError::UnexpectedEnd {
+            len: bytes.len(),
+            expected: end,
+        })?;
+        proof.push(node.to_vec());
+        at = end;
+    }
+    if at != bytes.len() {
+        return Err(DecodeError::TrailingBytes(bytes.len() - at));
+    }
+    Ok((root, proof))
+}
+
+fn encode_len(len: usize) -> [u8; LEN_LEN] {
+    u32::try_from(len).expect("framed proof length over u32::MAX").to_le_bytes()
+}
+
+fn read_len(bytes: &[u8], at: usize) -> Result<usize, DecodeError> {
+    let encoded = bytes.get(at..at + LEN_LEN).ok_or(DecodeError::UnexpectedEnd {
+        len: bytes.len(),
+        expected: at + LEN_LEN,
+    })?;
+    let mut len = [0; LEN_LEN];
+    len.copy_from_slice(encoded);
+    Ok(u32::from_le_bytes(len) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_trie;
+    use crate::{generate_proof, verify_proof_raw_key, EthereumLayout};
+
+    #[test]
+    fn it_round_trips_framed_proof() {
+        let entries: Vec<_> = (0..8u8).map(|i| (vec![i; 20], vec![i; 40])).collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[5].0).unwrap();
+
+        let bytes = encode_framed_proof(root, &proof);
+        assert_eq!(bytes[..2], [MAGIC, VERSION]);
+        let (decoded_root, decoded) = decode_framed_proof(&bytes).unwrap();
+        assert_eq!((decoded_root, &decoded), (root, &proof));
+        verify_proof_raw_key::<EthereumLayout>(
+            &decoded_root,
+            &decoded,
+            &entries[5].0,
+            Some(&entries[5].1),
+        )
+        .expect("Failed to verify framed proof");
+
+        let empty = encode_framed_proof(B256::ZERO, &[]);
+        assert_eq!(empty.len(), HEADER_LEN);
+        assert_eq!(decode_framed_proof(&empty), Ok((B256::ZERO, Vec::new())));
+    }
+
+    #[test]
+    fn it_rejects_bad_magic_and_version() {
+        let bytes = encode_framed_proof(B256::repeat_byte(0x11), &[vec![0x80]]);
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = 0x00;
+        assert_eq!(decode_framed_proof(&bad_magic), Err(DecodeError::BadMagic(0x00)));
+        let mut bad_version = bytes.clone();
+        bad_version[1] = VERSION + 1;
+        assert_eq!(
+            decode_framed_proof(&bad_version),
+            Err(DecodeError::UnsupportedVersion(VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn it_rejects_truncated_and_trailing_bytes() {
+        let bytes = encode_framed_proof(B256::repeat_byte(0x11), &[vec![0x80], vec![0xab; 40]]);
+        for len in 0..bytes.len() {
+            assert!(matches!(
+                decode_framed_proof(&bytes[..len]),
+                Err(DecodeError::UnexpectedEnd { .. })
+            ));
+        }
+        let mut trailing = bytes.clone();
+        trailing.push(0x00);
+        assert_eq!(decode_framed_proof(&trailing), Err(DecodeError::TrailingBytes(1)));
+
+        // A node length running past the end of the address space fails instead of overflowing.
+        let mut huge_len = bytes[..HEADER_LEN + LEN_LEN].to_vec();
+        huge_len[HEADER_LEN..].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            decode_framed_proof(&huge_len),
+            Err(DecodeError::UnexpectedEnd { .. })
+        ));
+
+        // A node count far beyond the input fails without allocating for it.
+        let mut huge_count = bytes[..HEADER_LEN].to_vec();
+        huge_count[34..].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            decode_framed_proof(&huge_count),
+            Err(DecodeError::UnexpectedEnd { .. })
+        ));
+    }
+}
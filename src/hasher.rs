@@ -1,3 +1,5 @@
+use crate::types::B256;
+
 use core::hash::Hasher;
 use tiny_keccak::{Hasher as CoreHasher, Keccak};
 
@@ -37,7 +39,7 @@ impl Default for Keccak256Hasher {
 pub struct KeccakHasher;
 
 impl hash_db::Hasher for KeccakHasher {
-    type Out = [u8; 32];
+    type Out = B256;
     const LENGTH: usize = 32;
 
     fn hash(x: &[u8]) -> Self::Out {
@@ -47,8 +49,68 @@ impl hash_db::Hasher for KeccakHasher {
         keccak_256.update(x);
         keccak_256.finalize(&mut output);
 
-        output
+        B256::from(output)
     }
 
     type StdHasher = Keccak256Hasher;
 }
+
+/// Keccak-256 of many inputs in a loop, such as storage slots, each written to a buffer of the
+/// caller's. Every call starts from a copy of the initial state, which costs about as much as the
+/// fresh state [`KeccakHasher`] sets up, so this is no faster than [`KeccakHasher::hash`].
+#[derive(Clone)]
+pub struct KeccakState {
+    initial: Keccak,
+}
+
+impl KeccakState {
+    pub fn new() -> Self {
+        Self {
+            initial: Keccak::v256(),
+        }
+    }
+
+    /// Write the Keccak-256 of `input` to `out`.
+    pub fn hash_into(&self, input: &[u8], out: &mut [u8; 32]) {
+        let mut keccak = self.initial.clone();
+        keccak.update(input);
+        keccak.finalize(out);
+    }
+}
+
+impl Default for KeccakState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hash_db::Hasher;
+
+    #[test]
+    fn it_hashes_known_keccak_vectors() {
+        assert_eq!(
+            KeccakHasher::hash(b""),
+            "0xc5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+                .parse::<B256>()
+                .unwrap()
+        );
+        assert_eq!(
+            KeccakHasher::hash(&crate::node_codec::NULL_NODE),
+            crate::EMPTY_TRIE_ROOT
+        );
+    }
+
+    #[test]
+    fn it_hashes_with_reused_state_as_stateless_hasher() {
+        let state = KeccakState::new();
+        let mut out = [0u8; 32];
+        let inputs: [&[u8]; 3] = [b"", b"abc", &[0xab; 200]];
+        for input in inputs {
+            state.hash_into(input, &mut out);
+            assert_eq!(B256::from(out), KeccakHasher::hash(input));
+        }
+    }
+}
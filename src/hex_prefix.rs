@@ -0,0 +1,83 @@
+//! The hex-prefix (compact) encoding of the partial paths of leaf and extension nodes, for
+//! inspecting raw nodes. The high nibble of the first byte holds the flags: `2` for a leaf and `1`
+//! for an odd number of nibbles, in which case its low nibble is the first nibble of the path.
+
+use crate::rstd::vec::Vec;
+
+const LEAF_FLAG: u8 = 0x2;
+const ODD_FLAG: u8 = 0x1;
+
+/// Decode a hex-prefix encoded path into its nibbles and whether it belongs to a leaf.
+///
+/// Panics if `encoded` is empty, as every encoded path has at least the flags byte.
+pub fn decode(encoded: &[u8]) -> (Vec<u8>, bool) {
+    let (first, rest) = encoded.split_first().expect("hex-prefix path without flags byte");
+    let flags = first >> 4;
+
+    let mut nibbles = Vec::with_capacity(rest.len() * 2 + 1);
+    if flags & ODD_FLAG == ODD_FLAG {
+        nibbles.push(first & 0x0f);
+    }
+    for byte in rest {
+        nibbles.push(byte >> 4);
+        nibbles.push(byte & 0x0f);
+    }
+    (nibbles, flags & LEAF_FLAG == LEAF_FLAG)
+}
+
+/// Hex-prefix encode `nibbles` as the path of a leaf or of an extension. Each nibble must be less
+/// than 16.
+pub fn encode(nibbles: &[u8], is_leaf: bool) -> Vec<u8> {
+    debug_assert!(nibbles.iter().all(|nibble| *nibble < 16));
+    let mut flags = if is_leaf { LEAF_FLAG } else { 0 };
+
+    let mut encoded = Vec::with_capacity(nibbles.len() / 2 + 1);
+    let pairs = if nibbles.len() % 2 == 1 {
+        flags |= ODD_FLAG;
+        encoded.push(flags << 4 | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        encoded.push(flags << 4);
+        nibbles
+    };
+    encoded.extend(pairs.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_codec::{decode_node, OwnedNode};
+
+    #[test]
+    fn it_round_trips_all_prefix_cases() {
+        let cases: [(&[u8], &[u8], bool); 6] = [
+            (&[0x00, 0xab], &[0xa, 0xb], false),
+            (&[0x1a, 0xbc], &[0xa, 0xb, 0xc], false),
+            (&[0x20, 0x12], &[0x1, 0x2], true),
+            (&[0x31, 0x23], &[0x1, 0x2, 0x3], true),
+            (&[0x00], &[], false),
+            (&[0x20], &[], true),
+        ];
+
+        for (encoded, nibbles, is_leaf) in cases {
+            assert_eq!(decode(encoded), (nibbles.to_vec(), is_leaf));
+            assert_eq!(encode(nibbles, is_leaf), encoded.to_vec());
+        }
+    }
+
+    #[test]
+    fn it_decodes_path_of_encoded_leaf() {
+        let mut stream = rlp::RlpStream::new_list(2);
+        stream.append(&encode(&[0x1, 0x2, 0x3], true));
+        stream.append(&b"hello".to_vec());
+        let node = stream.out();
+
+        let path: Vec<u8> = rlp::Rlp::new(&node).val_at(0).unwrap();
+        assert_eq!(decode(&path), (vec![0x1, 0x2, 0x3], true));
+        assert!(matches!(
+            decode_node(&node),
+            Ok(OwnedNode::Leaf { path, .. }) if path == [0x1, 0x2, 0x3]
+        ));
+    }
+}
@@ -0,0 +1,154 @@
+//! How the keys of a trie are derived from the keys they are looked up with, so that one pair of
+//! proof functions serves the state and storage tries, whose keys are hashed, and the transaction
+//! and receipt tries, whose keys are the RLP of an index.
+
+use crate::rstd::vec::Vec;
+use crate::{generate_proof_for_key, verify_proof_hashed_key, KeccakHasher, VerifyError};
+
+use hash_db::{HashDBRef, Hasher};
+use trie_db::{CError, DBValue, Result as TrieResult, TrieHash, TrieLayout};
+
+/// Derivation of the path of a key in a trie from the key it is looked up with.
+pub trait KeyScheme {
+    fn trie_key(raw: &[u8]) -> Vec<u8>;
+}
+
+/// Keys hashed with Keccak-256, as in the state and storage tries.
+pub struct Keccak256Key;
+
+impl KeyScheme for Keccak256Key {
+    fn trie_key(raw: &[u8]) -> Vec<u8> {
+        KeccakHasher::hash(raw).to_vec()
+    }
+}
+
+/// Keys that are the RLP of an index, as in the transaction and receipt tries of a block. The raw
+/// key is the index in big-endian bytes, such as `index.to_be_bytes()`; leading zeros are ignored.
+pub struct RlpIndexKey;
+
+impl KeyScheme for RlpIndexKey {
+    fn trie_key(raw: &[u8]) -> Vec<u8> {
+        let leading_zeros = raw.iter().take_while(|byte| **byte == 0).count();
+        rlp::encode(&raw[leading_zeros..].to_vec()).to_vec()
+    }
+}
+
+/// Keys used as they are, as in tries built directly with `TrieDBMut`.
+pub struct IdentityKey;
+
+impl KeyScheme for IdentityKey {
+    fn trie_key(raw: &[u8]) -> Vec<u8> {
+        raw.to_vec()
+    }
+}
+
+/// Generate the proof of `raw_key` in the trie at `root`, with its path derived by `K`.
+pub fn generate_proof_with_scheme<L, K>(
+    db: &dyn HashDBRef<L::Hash, DBValue>,
+    root: &TrieHash<L>,
+    raw_key: &[u8],
+) -> TrieResult<(Vec<Vec<u8>>, Option<Vec<u8>>), TrieHash<L>, CError<L>>
+where
+    L: TrieLayout,
+    K: KeyScheme,
+{
+    generate_proof_for_key::<L>(db, root, &K::trie_key(raw_key))
+}
+
+/// Verify the proof of `raw_key` in the trie at `root`, with its path derived by `K`.
+pub fn verify_proof_with_scheme<L, K>(
+    root: &TrieHash<L>,
+    proof: &[Vec<u8>],
+    raw_key: &[u8],
+    expected_value: Option<&[u8]>,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+    K: KeyScheme,
+{
+    verify_proof_hashed_key::<L>(root, proof, &K::trie_key(raw_key), expected_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::tx_trie_key;
+    use crate::tests::test_trie;
+    use crate::{empty_db, EthereumLayout};
+
+    use trie_db::{TrieDBMutBuilder, TrieMut};
+
+    #[test]
+    fn it_derives_trie_keys_for_each_scheme() {
+        assert_eq!(Keccak256Key::trie_key(b"abc"), KeccakHasher::hash(b"abc").to_vec());
+        for index in [0u64, 1, 127, 128, 256, u64::MAX] {
+            assert_eq!(RlpIndexKey::trie_key(&index.to_be_bytes()), tx_trie_key(index));
+        }
+        assert_eq!(RlpIndexKey::trie_key(&[]), tx_trie_key(0));
+        assert_eq!(IdentityKey::trie_key(b"abc"), b"abc".to_vec());
+    }
+
+    #[test]
+    fn it_proves_keys_of_hashed_trie() {
+        let entries = vec![
+            (b"key-1".to_vec(), vec![0xab; 40]),
+            (b"key-2".to_vec(), vec![0xcd; 40]),
+        ];
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+
+        let (proof, value) =
+            generate_proof_with_scheme::<EthereumLayout, Keccak256Key>(&db, &root, b"key-1")
+                .unwrap();
+        assert_eq!(value, Some(vec![0xab; 40]));
+        verify_proof_with_scheme::<EthereumLayout, Keccak256Key>(
+            &root,
+            &proof,
+            b"key-1",
+            Some(&[0xab; 40]),
+        )
+        .expect("Failed to verify proof of hashed key");
+        assert!(verify_proof_with_scheme::<EthereumLayout, IdentityKey>(
+            &root,
+            &proof,
+            b"key-1",
+            Some(&[0xab; 40])
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn it_proves_keys_of_index_and_identity_tries() {
+        let mut db = empty_db();
+        let mut root = Default::default();
+        {
+            let mut trie = TrieDBMutBuilder::<EthereumLayout>::new(&mut db, &mut root).build();
+            for index in 0..20u64 {
+                trie.insert(&tx_trie_key(index), &[index as u8; 40]).unwrap();
+            }
+        }
+
+        let index = 17u64.to_be_bytes();
+        let (proof, value) =
+            generate_proof_with_scheme::<EthereumLayout, RlpIndexKey>(&db, &root, &index).unwrap();
+        assert_eq!(value, Some(vec![17; 40]));
+        verify_proof_with_scheme::<EthereumLayout, RlpIndexKey>(
+            &root,
+            &proof,
+            &index,
+            Some(&[17; 40]),
+        )
+        .expect("Failed to verify proof of index key");
+
+        let raw_key = tx_trie_key(17);
+        let (proof, _) =
+            generate_proof_with_scheme::<EthereumLayout, IdentityKey>(&db, &root, &raw_key)
+                .unwrap();
+        verify_proof_with_scheme::<EthereumLayout, IdentityKey>(
+            &root,
+            &proof,
+            &raw_key,
+            Some(&[17; 40]),
+        )
+        .expect("Failed to verify proof of identity key");
+    }
+}
@@ -1,24 +1,40 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+pub mod block;
+#[cfg(feature = "cache")]
+pub mod cache;
 pub mod eip1186;
+pub mod framed;
 pub mod node_codec;
 pub mod hasher;
+pub mod hex_prefix;
+pub mod key_scheme;
+pub mod state;
+pub mod types;
+pub mod utils;
+#[cfg(feature = "serde")]
+mod serde_hex;
+#[cfg(feature = "ssz")]
+pub mod ssz;
+#[cfg(feature = "revm")]
+pub mod stateless;
 
 #[cfg(feature = "std")]
 mod rstd {
     pub use core::fmt::Debug;
     pub use std::error::Error;
     pub use std::format;
-    pub use std::{collections::BTreeMap, result, vec};
+    pub use std::{boxed::Box, collections::{BTreeMap, BTreeSet}, result, string::String, vec};
 }
 
 #[cfg(not(feature = "std"))]
 mod rstd {
     extern crate alloc;
     extern crate trie_db;
-    pub use alloc::collections::BTreeMap;
+    pub use alloc::boxed::Box;
+    pub use alloc::collections::{BTreeMap, BTreeSet};
     pub use alloc::format;
-    pub use alloc::string::ToString;
+    pub use alloc::string::{String, ToString};
     pub use alloc::vec;
     pub use core::result;
 
@@ -26,30 +42,102 @@ mod rstd {
     impl<T> Error for T {}
 }
 
-pub use eip1186::{RlpTrieLayout, VerifyError};
-pub use hasher::KeccakHasher;
+pub use eip1186::{RlpTrieLayout, TrieDbProofLayout, VerifyError, VerifyOptions, MAX_PROOF_DEPTH};
+pub use hasher::{KeccakHasher, KeccakState};
+pub use state::{split_proofs, verify_against_header, verify_proofs, verify_proofs_detailed};
+/// The trie-db types that appear in the signatures of this crate, so that callers need not depend
+/// on a matching version of trie-db themselves.
+pub use trie_db::{CError, DBValue, Recorder, Trie, TrieHash, TrieLayout};
 
-use hash_db::{HashDBRef, Hasher};
-use node_codec::NULL_NODE;
-use rstd::vec::Vec;
-use trie_db::{DBValue, Result as TrieResult, TrieHash, CError, TrieLayout, TrieDBBuilder, Recorder, Trie, NibbleSlice};
+use hash_db::{HashDB, HashDBRef, Hasher, EMPTY_PREFIX};
+use node_codec::HASHED_NULL_NODE;
+use rstd::{vec::Vec, BTreeMap, Box};
+use trie_db::{Result as TrieResult, TrieDBBuilder, TrieError, NibbleSlice, NodeCodec, SecTrieDBMut, TrieMut};
+use trie_db::node::{decode_hash, Node, NodeHandle, NodeHandlePlan, NodePlan, Value};
+use types::{IntoB256, B256};
 use memory_db::{MemoryDB, HashKey};
-use eip1186::process_node;
+use eip1186::{
+  collect_leaves, collect_path, encode_compact_node, index_nodes, process_node, process_path_strict,
+  process_node_with, reassemble_path, walk_path_strict,
+};
 
 pub type EthereumLayout = RlpTrieLayout<KeccakHasher>;
 
 pub type EthereumMemoryDB =
     MemoryDB<<RlpTrieLayout<KeccakHasher> as TrieLayout>::Hash, HashKey<<RlpTrieLayout<KeccakHasher> as TrieLayout>::Hash>, DBValue>;
 
+/// Root of the empty trie, keccak of the RLP empty string.
+pub const EMPTY_TRIE_ROOT: B256 = B256::new(HASHED_NULL_NODE);
+
 pub fn empty_db() -> EthereumMemoryDB {
-  EthereumMemoryDB::new(&NULL_NODE)
+  empty_db_for::<EthereumLayout>()
+}
+
+/// An empty database for tries of layout `L`, holding the layout's empty node as its null node so
+/// that a trie built in it starts from the layout's empty root.
+pub fn empty_db_for<L: TrieLayout>() -> MemoryDB<L::Hash, HashKey<L::Hash>, DBValue> {
+  MemoryDB::new(<L::Codec as NodeCodec>::empty_node())
+}
+
+/// Compute the root of the trie holding `entries`, with keys hashed as in the state and storage
+/// tries. Useful for precomputing the root a set of proofs is expected to verify against. The
+/// layout's hash must be 32 bytes.
+pub fn compute_state_root<L>(entries: &[(Vec<u8>, Vec<u8>)]) -> B256
+where
+  L: TrieLayout,
+{
+  let mut db = empty_db_for::<L>();
+  let mut root = Default::default();
+  {
+    let mut trie = SecTrieDBMut::<L>::new(&mut db, &mut root);
+    for (key, value) in entries.iter() {
+      trie.insert(key, value).expect("in-memory trie insertion does not fail");
+    }
+  }
+  B256::from_slice(root.as_ref())
+}
+
+/// Insert `entries` in order into a trie keyed as by [`compute_state_root`], and return each key
+/// along with the root right after its insertion, so that the insertion that moved a root away
+/// from the expected one can be found. The last root is the one of the whole trie.
+pub fn root_after_each<L>(entries: &[(Vec<u8>, Vec<u8>)]) -> Vec<(Vec<u8>, B256)>
+where
+  L: TrieLayout,
+{
+  let mut db = empty_db_for::<L>();
+  let mut root = Default::default();
+  let mut trie = SecTrieDBMut::<L>::new(&mut db, &mut root);
+  entries
+    .iter()
+    .map(|(key, value)| {
+      trie.insert(key, value).expect("in-memory trie insertion does not fail");
+      (key.clone(), B256::from_slice(trie.root().as_ref()))
+    })
+    .collect()
 }
 
+/// Generate the proof of `key` in the trie at `root`. The key is hashed before the lookup, as in
+/// the state and storage tries, so the proof pairs with [`verify_proof_raw_key`] given the same
+/// key. For tries whose keys are not hashed use [`generate_proof_for_key`].
 pub fn generate_proof<L>(
 	db: &dyn HashDBRef<L::Hash, DBValue>,
 	root: &TrieHash<L>,
 	key: &[u8],
 ) -> TrieResult<(Vec<Vec<u8>>, Option<Vec<u8>>), TrieHash<L>, CError<L>>
+where
+	L: TrieLayout,
+{
+  generate_proof_for_key::<L>(db, root, <L::Hash>::hash(key).as_ref())
+}
+
+/// Generate the proof of `trie_key` exactly as it appears in the trie at `root`, without hashing
+/// it, for tries built with `TrieDBMut` or keys that are already hashed. The proof pairs with
+/// [`verify_proof_hashed_key`] given the same key.
+pub fn generate_proof_for_key<L>(
+	db: &dyn HashDBRef<L::Hash, DBValue>,
+	root: &TrieHash<L>,
+	trie_key: &[u8],
+) -> TrieResult<(Vec<Vec<u8>>, Option<Vec<u8>>), TrieHash<L>, CError<L>>
 where
 	L: TrieLayout,
 {
@@ -57,7 +145,7 @@ where
 
   let item = {
 		let trie = TrieDBBuilder::<L>::new(db, root).with_recorder(&mut recorder).build();
-		trie.get(<L::Hash>::hash(key).as_ref())?
+		trie.get(trie_key)?
 	};
 
 	let proof: Vec<Vec<u8>> = recorder.drain().into_iter().map(|r| r.data).collect();
@@ -65,23 +153,710 @@ where
 	Ok((proof, item))
 }
 
+/// Generate a proof with its nodes sorted by hash instead of in traversal order. The result is a
+/// canonical witness for the key that does not depend on how the trie was built or traversed, and
+/// is verified with [`verify_proof_unordered`].
+pub fn generate_proof_sorted<L>(
+  db: &dyn HashDBRef<L::Hash, DBValue>,
+  root: &TrieHash<L>,
+  key: &[u8],
+) -> TrieResult<(Vec<Vec<u8>>, Option<Vec<u8>>), TrieHash<L>, CError<L>>
+where
+  L: TrieLayout,
+{
+  let (mut proof, item) = generate_proof::<L>(db, root, key)?;
+  proof.sort_by_cached_key(|node| <L::Hash>::hash(node));
+  Ok((proof, item))
+}
+
+/// Failure of [`generate_proof_verified`].
+#[derive(PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum GenerateProofError<HO, CE> {
+  /// The lookup of the key failed, as for [`generate_proof`].
+  Trie(Box<TrieError<HO, CE>>),
+  /// The generated proof does not verify against the root with the value the lookup returned,
+  /// which points to a bug in the node codec or the trie rather than to the database.
+  SelfCheck(VerifyError<HO, CE>),
+}
+
+/// Generate the proof of `key` in the trie at `root` as [`generate_proof`], then verify it with
+/// [`verify_proof_raw_key`] against the same root and the value the lookup returned, so that a
+/// proof that would not verify is caught where it is generated.
+pub fn generate_proof_verified<L>(
+  db: &dyn HashDBRef<L::Hash, DBValue>,
+  root: &TrieHash<L>,
+  key: &[u8],
+) -> Result<(Vec<Vec<u8>>, Option<Vec<u8>>), GenerateProofError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let (proof, item) = generate_proof::<L>(db, root, key).map_err(GenerateProofError::Trie)?;
+  verify_proof_raw_key::<L>(root, &proof, key, item.as_deref()).map_err(GenerateProofError::SelfCheck)?;
+  Ok((proof, item))
+}
+
+/// Yield the nodes of the proof of `key` in the trie at `root` one at a time, root first, as
+/// [`generate_proof`] returns them, so that a large proof can be sent on without being held in
+/// memory. The key is hashed as by [`generate_proof`]. Each node is read from `db` only when the
+/// previous one has been yielded. A root missing from `db` yields `InvalidStateRoot`, any other
+/// missing node `IncompleteDatabase`, and a node that does not decode `DecoderError`, as
+/// [`generate_proof`] reports them; the stream ends after the first error.
+pub fn generate_proof_stream<'db, L>(
+  db: &'db dyn HashDBRef<L::Hash, DBValue>,
+  root: &TrieHash<L>,
+  key: &[u8],
+) -> impl Iterator<Item = TrieResult<Vec<u8>, TrieHash<L>, CError<L>>> + 'db
+where
+  L: TrieLayout + 'db,
+{
+  let key = <L::Hash>::hash(key).as_ref().to_vec();
+  let root = *root;
+  let mut offset = 0;
+  let mut next = Some(root);
+  core::iter::from_fn(move || {
+    let hash = next.take()?;
+    let data = match db.get(&hash, EMPTY_PREFIX) {
+      Some(data) => data,
+      None if hash == root => return Some(Err(Box::new(TrieError::InvalidStateRoot(hash)))),
+      None => return Some(Err(Box::new(TrieError::IncompleteDatabase(hash)))),
+    };
+    match next_node_hash::<L>(&data, &key, &mut offset) {
+      Ok(hash) => next = hash,
+      Err(err) => return Some(Err(Box::new(TrieError::DecoderError(hash, err)))),
+    }
+    Some(Ok(data))
+  })
+}
+
+/// The hash of the node that `key`, from nibble `offset` on, leads to from the node encoded in
+/// `data`, descending through inline nodes and advancing `offset` past the nibbles consumed. `None`
+/// if the lookup ends at or under this node, and the codec error if `data` or a node inlined in it
+/// does not decode.
+fn next_node_hash<L: TrieLayout>(
+  data: &[u8],
+  key: &[u8],
+  offset: &mut usize,
+) -> Result<Option<TrieHash<L>>, CError<L>> {
+  let mut data = data;
+  loop {
+    let mut partial = NibbleSlice::new_offset(key, *offset);
+    let handle = match <L::Codec as NodeCodec>::decode(data)? {
+      Node::Empty | Node::Leaf(..) => return Ok(None),
+      Node::Extension(nib, handle) => {
+        if !partial.starts_with(&nib) {
+          return Ok(None);
+        }
+        partial.advance(nib.len());
+        handle
+      }
+      Node::Branch(mut children, _) => {
+        if partial.is_empty() {
+          return Ok(None);
+        }
+        let handle = match children[partial.at(0) as usize].take() {
+          Some(handle) => handle,
+          None => return Ok(None),
+        };
+        partial.advance(1);
+        handle
+      }
+      Node::NibbledBranch(nib, mut children, _) => {
+        if !partial.starts_with(&nib) || partial.len() == nib.len() {
+          return Ok(None);
+        }
+        partial.advance(nib.len());
+        let handle = match children[partial.at(0) as usize].take() {
+          Some(handle) => handle,
+          None => return Ok(None),
+        };
+        partial.advance(1);
+        handle
+      }
+    };
+    *offset = key.len() * 2 - partial.len();
+    match handle {
+      NodeHandle::Hash(hash) => return Ok(decode_hash::<L::Hash>(hash)),
+      NodeHandle::Inline(inline) => data = inline,
+    }
+  }
+}
+
 /// Verify a compact proof for key-value pairs in a trie given a root hash.
+#[deprecated(
+  note = "ambiguous about key hashing: use `verify_proof_hashed_key` with the trie key, or `verify_proof_raw_key` to hash it"
+)]
 pub fn verify_proof<'a, L>(
   root: &<L::Hash as Hasher>::Out,
   proof: &'a [Vec<u8>],
   raw_key: &'a [u8],
   expected_value: Option<&[u8]>,
-) -> Result<(), VerifyError<'a, TrieHash<L>, CError<L>>>
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  verify_proof_hashed_key::<L>(root, proof, raw_key, expected_value)
+}
+
+/// Verify a proof under `key` as it appears in the trie, such as `keccak256(address)` in the state
+/// trie or `keccak256(slot)` in a storage trie. The key is not hashed again. The first node is
+/// hashed and compared to `root` before anything else, failing with [`VerifyError::RootMismatch`].
+pub fn verify_proof_hashed_key<'a, L>(
+  root: &<L::Hash as Hasher>::Out,
+  proof: &'a [Vec<u8>],
+  key: &'a [u8],
+  expected_value: Option<&[u8]>,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  if proof.is_empty() {
+      return Err(VerifyError::IncompleteProof);
+  }
+
+  let key = NibbleSlice::new(key);
+  process_node::<L, _>(Some(root), &proof[0], key, expected_value, &proof[1..], 0)
+}
+
+/// Verify a proof under `key` as it appears in the trie, as [`verify_proof_hashed_key`] does, with
+/// the depth limit, minimality check and value comparison of `options`. With the default options
+/// the two verify alike.
+pub fn verify_proof_with_options<L>(
+  root: &<L::Hash as Hasher>::Out,
+  proof: &[Vec<u8>],
+  key: &[u8],
+  expected_value: Option<&[u8]>,
+  options: &VerifyOptions,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
 where
   L: TrieLayout,
-  <L::Hash as Hasher>::Out: 'a,
 {
   if proof.is_empty() {
       return Err(VerifyError::IncompleteProof);
   }
 
+  let nibbles = NibbleSlice::new(key);
+  process_node_with::<L, _>(Some(root), &proof[0], nibbles, expected_value, &proof[1..], 0, options)?;
+  if options.check_minimal && !is_minimal_proof::<L>(root, proof, key)? {
+    return Err(VerifyError::NonMinimalProof);
+  }
+  Ok(())
+}
+
+/// Verify a proof under the unhashed `raw_key`, such as an address or a storage slot, which is
+/// hashed into the trie key first. This is the counterpart of [`generate_proof`], which hashes the
+/// key the same way.
+pub fn verify_proof_raw_key<L>(
+  root: &<L::Hash as Hasher>::Out,
+  proof: &[Vec<u8>],
+  raw_key: &[u8],
+  expected_value: Option<&[u8]>,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let key = <L::Hash as Hasher>::hash(raw_key);
+  verify_proof_hashed_key::<L>(root, proof, key.as_ref(), expected_value)
+}
+
+/// Verify the proof of `raw_key`, supplied in path order and hashed as by
+/// [`verify_proof_raw_key`], and return what `predicate` says of the value it proves, or of `None`
+/// if it proves the key absent. The predicate only ever sees an authenticated value, so it can
+/// check properties of the value, such as a balance above a threshold, instead of the caller
+/// supplying the value itself.
+pub fn verify_proof_predicate<L, F>(
+  root: &<L::Hash as Hasher>::Out,
+  proof: &[Vec<u8>],
+  raw_key: &[u8],
+  predicate: F,
+) -> Result<bool, VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+  F: Fn(Option<&[u8]>) -> bool,
+{
+  let key = <L::Hash as Hasher>::hash(raw_key);
+  let end = walk_path_strict::<L, _>(root, proof, NibbleSlice::new(key.as_ref()), None)?;
+  Ok(predicate(end.value))
+}
+
+/// Verify a proof given as hex strings, as returned by JSON-RPC, under the trie `key` as
+/// [`verify_proof_hashed_key`]. The `0x` prefix is optional. Fails with [`VerifyError::HexDecode`]
+/// if an input is not valid hex or the root is not the length of a hash.
+pub fn verify_proof_hex<L>(
+  root_hex: &str,
+  proof_hex: &[&str],
+  key_hex: &str,
+  expected_hex: Option<&str>,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let mut root = TrieHash::<L>::default();
+  hex::decode_to_slice(strip_hex_prefix(root_hex), root.as_mut()).map_err(VerifyError::HexDecode)?;
+  let proof = proof_hex
+    .iter()
+    .map(|node| decode_hex(node))
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(VerifyError::HexDecode)?;
+  let key = decode_hex(key_hex).map_err(VerifyError::HexDecode)?;
+  let expected_value = expected_hex.map(decode_hex).transpose().map_err(VerifyError::HexDecode)?;
+  verify_proof_hashed_key::<L>(&root, &proof, &key, expected_value.as_deref())
+}
+
+fn strip_hex_prefix(value: &str) -> &str {
+  value.strip_prefix("0x").unwrap_or(value)
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, hex::FromHexError> {
+  hex::decode(strip_hex_prefix(value))
+}
+
+/// Verify a proof in the Ethereum trie under the trie `key`, as [`verify_proof_hashed_key`], with
+/// the root given as a `B256`, an `H256` or a `[u8; 32]`.
+pub fn verify_proof_root(
+  root: impl IntoB256,
+  proof: &[Vec<u8>],
+  key: &[u8],
+  expected_value: Option<&[u8]>,
+) -> Result<(), VerifyError<B256, CError<EthereumLayout>>> {
+  verify_proof_hashed_key::<EthereumLayout>(&root.into_b256(), proof, key, expected_value)
+}
+
+/// Verify a proof against several candidate roots, such as the state roots of competing blocks
+/// during a reorg, and return the index of the first root it verifies against. The root node is
+/// hashed once and compared against each candidate, rather than verifying the proof once per root.
+pub fn verify_proof_any<'a, L>(
+  roots: &[<L::Hash as Hasher>::Out],
+  proof: &'a [Vec<u8>],
+  raw_key: &'a [u8],
+  expected_value: Option<&[u8]>,
+) -> Result<usize, VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let root_node = proof.first().ok_or(VerifyError::IncompleteProof)?;
+  let root_hash = <L::Hash as Hasher>::hash(root_node);
+  let index = roots.iter().position(|root| *root == root_hash).ok_or(VerifyError::NoMatchingRoot)?;
+
+  let key = NibbleSlice::new(raw_key);
+  process_node::<L, _>(None, root_node, key, expected_value, &proof[1..], 0)?;
+  Ok(index)
+}
+
+/// Verify a proof supplied in path order, re-hashing every node and checking it against the
+/// reference held by its parent, with the root node checked against `root`. Inline children are
+/// verified by value as part of their parent. Any node that does not match its reference fails
+/// with [`VerifyError::HashMismatch`] at its depth, so a tampered node is never reported as merely
+/// undecodable or out of order.
+pub fn verify_proof_strict<'a, L>(
+  root: &<L::Hash as Hasher>::Out,
+  proof: &'a [Vec<u8>],
+  raw_key: &'a [u8],
+  expected_value: Option<&[u8]>,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  process_path_strict::<L>(root, proof, NibbleSlice::new(raw_key), expected_value)
+}
+
+/// Verify a proof supplied in path order for a hashed 32-byte key, as [`verify_proof_strict`] does,
+/// without allocating: proof nodes are borrowed, and the key is walked in place as nibbles. Only a
+/// failure allocates, to report the remaining key or the value found. The layout's hash must be 32
+/// bytes.
+pub fn verify_proof_fixed<L>(
+  root: &[u8; 32],
+  proof: &[&[u8]],
+  key: &[u8; 32],
+  expected_value: Option<&[u8]>,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let mut root_hash = TrieHash::<L>::default();
+  root_hash.as_mut().copy_from_slice(root);
+  walk_path_strict::<L, _>(&root_hash, proof, NibbleSlice::new(key), None)?.check(expected_value)
+}
+
+/// The number of trie nodes on the path of a key through a proof supplied in path order, from the
+/// root to the leaf or to the node where the key leaves the trie, including nodes inlined in their
+/// parent. This is the depth a circuit verifying the proof must support. Every node on the path is
+/// checked as by [`verify_proof_strict`], but the value under the key is not.
+pub fn proof_depth<'a, L>(
+  root: &<L::Hash as Hasher>::Out,
+  proof: &'a [Vec<u8>],
+  raw_key: &'a [u8],
+) -> Result<usize, VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  walk_path_strict::<L, _>(root, proof, NibbleSlice::new(raw_key), None).map(|end| end.depth + 1)
+}
+
+/// Verify a proof supplied in path order as [`verify_proof_strict`] does, and return the
+/// authenticated value along with the hashes of the proof nodes the verification depended on, in
+/// path order from the root. The value is `None` for an exclusion proof.
+pub fn verify_proof_audited<'a, L>(
+  root: &<L::Hash as Hasher>::Out,
+  proof: &'a [Vec<u8>],
+  raw_key: &'a [u8],
+  expected_value: Option<&[u8]>,
+) -> Result<(Option<Vec<u8>>, Vec<TrieHash<L>>), VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let mut visited = Vec::new();
+  let end = walk_path_strict::<L, _>(root, proof, NibbleSlice::new(raw_key), Some(&mut visited))?;
+  end.check(expected_value)?;
+  Ok((end.value.map(|value| value.to_vec()), visited))
+}
+
+/// Find where the path of `raw_key`, which is hashed into the trie key first, leaves the trie of a
+/// proof supplied in path order, as checked by [`verify_proof_strict`]. Returns `None` if there is
+/// a value under the key, or the index of the first nibble of the hashed key that the trie has no
+/// path for, which for a key that ends in a branch without a value is the length of the key.
+pub fn find_divergence<L>(
+  root: &<L::Hash as Hasher>::Out,
+  proof: &[Vec<u8>],
+  raw_key: &[u8],
+) -> Result<Option<usize>, VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let key = <L::Hash as Hasher>::hash(raw_key);
+  let key = NibbleSlice::new(key.as_ref());
+  let end = walk_path_strict::<L, _>(root, proof, key, None)?;
+  Ok(end.value.is_none().then(|| key.len() - end.unmatched))
+}
+
+/// Reassemble the 32-byte trie key of the value a proof supplied in path order leads to, from the
+/// partials of its extensions and leaf and the child index taken at each branch. `raw_key` is
+/// hashed and only chooses the child at each branch, so the result equals `keccak(raw_key)` for a
+/// sound proof of the key and differs from it for a proof of another key. Every node is checked
+/// against its hash. Fails with [`VerifyError::NonExistingValue`] if the proof ends without a value
+/// or at a path that is not 64 nibbles long.
+pub fn verified_key_path<L>(
+  root: &<L::Hash as Hasher>::Out,
+  proof: &[Vec<u8>],
+  raw_key: &[u8],
+) -> Result<B256, VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let key = <L::Hash as Hasher>::hash(raw_key);
+  let key = NibbleSlice::new(key.as_ref());
+  let path = reassemble_path::<L>(root, proof, key)?;
+  if path.len() != 64 {
+    return Err(VerifyError::NonExistingValue((path.len()..key.len()).map(|i| key.at(i)).collect()));
+  }
+  let mut key_path = B256::ZERO;
+  for (byte, nibbles) in key_path.iter_mut().zip(path.chunks(2)) {
+    *byte = nibbles[0] << 4 | nibbles[1];
+  }
+  Ok(key_path)
+}
+
+/// Check that a proof supplied in path order is sound for a key, as [`verify_proof_strict`] does,
+/// but without checking the value under the key, or whether there is one. Useful to validate a
+/// witness before the values it proves are known.
+pub fn verify_proof_structure<'a, L>(
+  root: &<L::Hash as Hasher>::Out,
+  proof: &'a [Vec<u8>],
+  raw_key: &'a [u8],
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  walk_path_strict::<L, _>(root, proof, NibbleSlice::new(raw_key), None).map(|_| ())
+}
+
+/// Verify a proof whose nodes may be supplied in any order. Nodes are looked up by hash, so the
+/// proof may also contain duplicates or nodes unrelated to the key, as multiproofs often do.
+pub fn verify_proof_unordered<'a, L>(
+  root: &<L::Hash as Hasher>::Out,
+  proof: &'a [Vec<u8>],
+  raw_key: &'a [u8],
+  expected_value: Option<&[u8]>,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let nodes = index_nodes::<L>(proof);
+  let root_node = *nodes.get(root).ok_or(VerifyError::IncompleteProof)?;
+
+  let key = NibbleSlice::new(raw_key);
+  process_node::<L, _>(Some(root), root_node, key, expected_value, &nodes, 0)
+}
+
+/// Verify a proof given as a flat set of trie nodes with no guaranteed order and no marked root, as
+/// returned by snap sync and some debug endpoints. The root is the node that hashes to
+/// `expected_root`, wherever it is in `nodes`, and the rest of the path is looked up by hash as in
+/// [`verify_proof_unordered`]. Fails with [`VerifyError::NoMatchingRoot`] if no node hashes to
+/// `expected_root`.
+pub fn verify_proof_from_nodes<'a, L>(
+  expected_root: &<L::Hash as Hasher>::Out,
+  nodes: &'a [Vec<u8>],
+  raw_key: &'a [u8],
+  expected_value: Option<&[u8]>,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let nodes = index_nodes::<L>(nodes);
+  let root_node = *nodes.get(expected_root).ok_or(VerifyError::NoMatchingRoot)?;
+
   let key = NibbleSlice::new(raw_key);
-  process_node::<L>(Some(root), &proof[0], key, expected_value, &proof[1..])
+  process_node::<L, _>(Some(expected_root), root_node, key, expected_value, &nodes, 0)
+}
+
+/// Collect every leaf authenticated by a proof or multiproof, as the full nibble path of its trie
+/// key and its value, in key order. Nodes may be supplied in any order, and the walk only follows
+/// children that are among them. Fails with [`VerifyError::NodeReachedTwice`] if a node is
+/// referenced from more than one place, and with [`VerifyError::ProofTooDeep`] on a path deeper
+/// than [`MAX_PROOF_DEPTH`], so a crafted proof cannot make the walk outgrow its input.
+pub fn leaves_in_proof<L>(
+  root: &<L::Hash as Hasher>::Out,
+  proof: &[Vec<u8>],
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>, VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let nodes = index_nodes::<L>(proof);
+  collect_leaves::<L>(root, &nodes)
+}
+
+/// Recompute the smallest proof for a key from a larger set of trie nodes, such as a multiproof or
+/// a cached partial trie. The returned nodes are in path order, root first, and prove either the
+/// inclusion or the exclusion of the key.
+pub fn minimize_proof<'a, L>(
+  root: &<L::Hash as Hasher>::Out,
+  nodes: &'a [Vec<u8>],
+  raw_key: &'a [u8],
+) -> Result<Vec<Vec<u8>>, VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let nodes = index_nodes::<L>(nodes);
+  let path = collect_path::<L>(root, &nodes, NibbleSlice::new(raw_key))?;
+  Ok(path.into_iter().map(|node| node.to_vec()).collect())
+}
+
+/// Re-encode a proof of `trie_key` supplied in path order, as returned by [`generate_proof`] and
+/// `eth_getProof`, into the compact format of `trie_db::proof`, so it can be checked with
+/// `trie_db::proof::verify_proof` under [`TrieDbProofLayout`].
+///
+/// The formats hold the same nodes in the same order, but a compact proof leaves out what its
+/// verifier recomputes: the reference a node holds to the next hash-referenced node of the proof,
+/// and the value stored under the key, which the verifier is given instead. The omitted reference
+/// is encoded as the empty RLP list, which is never a valid child in the Ethereum trie and which
+/// only the codec of [`TrieDbProofLayout`] accepts, and the omitted value as the empty string.
+/// Inline children are kept, re-encoded in the same way. Nodes off the path are dropped. Fails if a
+/// node does not hash to the reference its parent holds.
+pub fn to_trie_db_proof<L>(
+  proof: &[Vec<u8>],
+  trie_key: &[u8],
+) -> Result<Vec<Vec<u8>>, VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let (root_node, rest) = proof.split_first().ok_or(VerifyError::IncompleteProof)?;
+  let mut compact = Vec::new();
+  let key = NibbleSlice::new(trie_key);
+  let (root_node, _) = encode_compact_node::<L>(root_node, key, rest, 0, &mut compact)?;
+  compact.insert(0, root_node);
+  Ok(compact)
+}
+
+/// Strip the value out of the leaf a proof ends at, for witnesses whose verifier already knows the
+/// value, and return it. The leaf is replaced by the same leaf holding the empty string. Returns
+/// `None`, leaving the proof as it is, if the proof does not end at a leaf. Verify the stripped
+/// proof with [`verify_proof_with_external_value`].
+pub fn strip_leaf_value(proof: &mut Vec<Vec<u8>>) -> Option<Vec<u8>> {
+  let last = proof.last_mut()?;
+  let (stripped, value) = match <EthereumLayout as TrieLayout>::Codec::decode(last).ok()? {
+    Node::Leaf(partial, Value::Inline(value)) => {
+      let stripped = <EthereumLayout as TrieLayout>::Codec::leaf_node(partial.right_iter(), partial.len(), Value::Inline(&[]));
+      (stripped, value.to_vec())
+    }
+    _ => return None,
+  };
+  *last = stripped;
+  Some(value)
+}
+
+/// Verify a proof whose leaf value was stripped by [`strip_leaf_value`] under the trie `key`, as
+/// [`verify_proof_hashed_key`] does, with `value` put back into the leaf before it is hashed. A
+/// leaf that still holds a value is verified as it is.
+pub fn verify_proof_with_external_value<L>(
+  root: &<L::Hash as Hasher>::Out,
+  proof: &[Vec<u8>],
+  key: &[u8],
+  value: &[u8],
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let mut proof = proof.to_vec();
+  let depth = proof.len().saturating_sub(1);
+  if let Some(last) = proof.last_mut() {
+    let restored = match <L::Codec as NodeCodec>::decode(last).map_err(|source| VerifyError::DecodeError { depth, source })? {
+      Node::Leaf(partial, Value::Inline(&[])) => Some(L::Codec::leaf_node(partial.right_iter(), partial.len(), Value::Inline(value))),
+      _ => None,
+    };
+    if let Some(restored) = restored {
+      *last = restored;
+    }
+  }
+  verify_proof_hashed_key::<L>(root, &proof, key, Some(value))
+}
+
+/// The canonical form of a set of proof nodes, for hashing or comparing proofs: one copy of each
+/// node, sorted by its Keccak-256 hash. Proofs holding the same nodes in any order, with or without
+/// duplicates, have byte-identical canonical forms.
+pub fn canonicalize_proof(proof: &[Vec<u8>]) -> Vec<Vec<u8>> {
+  index_nodes::<EthereumLayout>(proof).into_values().map(|node| node.to_vec()).collect()
+}
+
+/// Merge two proofs under the same root into one set holding each of their nodes once, the nodes of
+/// `a` first, for verification with [`verify_proof_unordered`]. If `raw_keys` are given, the
+/// merged set is checked to still hold the whole path of each, the key of `a` then the key of `b`,
+/// failing as [`minimize_proof`] does otherwise.
+pub fn merge_proofs<L>(
+  root: &<L::Hash as Hasher>::Out,
+  a: &[Vec<u8>],
+  b: &[Vec<u8>],
+  raw_keys: Option<(&[u8], &[u8])>,
+) -> Result<Vec<Vec<u8>>, VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let mut seen = BTreeMap::new();
+  let mut merged = Vec::new();
+  for node in a.iter().chain(b.iter()) {
+    if seen.insert(<L::Hash as Hasher>::hash(node), ()).is_none() {
+      merged.push(node.clone());
+    }
+  }
+  if let Some((key_a, key_b)) = raw_keys {
+    let nodes = index_nodes::<L>(&merged);
+    for key in [key_a, key_b] {
+      collect_path::<L>(root, &nodes, NibbleSlice::new(key))?;
+    }
+  }
+  Ok(merged)
+}
+
+/// Check that a proof holds only the nodes on the path of a key, each once, as [`minimize_proof`]
+/// returns. The nodes may be in any order. Fails as [`minimize_proof`] does if the path cannot be
+/// walked through the proof.
+pub fn is_minimal_proof<L>(
+  root: &<L::Hash as Hasher>::Out,
+  proof: &[Vec<u8>],
+  raw_key: &[u8],
+) -> Result<bool, VerifyError<TrieHash<L>, CError<L>>>
+where
+  L: TrieLayout,
+{
+  let nodes = index_nodes::<L>(proof);
+  // Nodes on a path are distinct, so the proof is minimal if it is no larger than the path.
+  let path = collect_path::<L>(root, &nodes, NibbleSlice::new(raw_key))?;
+  Ok(path.len() == proof.len())
+}
+
+/// Rebuild a partial trie database from proof nodes, such as the proofs of several keys under the
+/// same root, so that the proven keys can be read back with `TrieDB`.
+pub fn build_partial_db(nodes: &[Vec<u8>]) -> EthereumMemoryDB {
+  let mut db = empty_db();
+  insert_proof_nodes(&mut db, nodes);
+  db
+}
+
+/// Insert proof nodes into `db`, each under its Keccak hash, as `TrieDB` looks nodes up. The
+/// database is keyed by `HashKey`, which ignores the prefix, so the nodes are inserted with
+/// `EMPTY_PREFIX` and are found wherever in the trie they are referenced from. A node inserted
+/// twice is counted twice, and stays in the database until removed as often.
+pub fn insert_proof_nodes(db: &mut EthereumMemoryDB, nodes: &[Vec<u8>]) {
+  for node in nodes.iter() {
+    db.insert(EMPTY_PREFIX, node);
+  }
+}
+
+/// Find the nodes stored in `db` that cannot be reached from `root`, such as those left by a
+/// malformed witness in a database rebuilt with [`build_partial_db`]. The hashes are returned in
+/// order.
+pub fn find_orphan_nodes<L>(
+  db: &MemoryDB<L::Hash, HashKey<L::Hash>, DBValue>,
+  root: &TrieHash<L>,
+) -> Vec<TrieHash<L>>
+where
+  L: TrieLayout,
+{
+  let reachable: BTreeMap<_, ()> = iter_nodes::<L>(db, root).map(|(hash, _, _)| (hash, ())).collect();
+  let mut orphans: Vec<_> = db
+    .keys()
+    .into_iter()
+    .filter(|(hash, rc)| *rc > 0 && !reachable.contains_key(hash))
+    .map(|(hash, _)| hash)
+    .collect();
+  orphans.sort();
+  orphans
+}
+
+/// Walk the trie at `root` depth first, children in nibble order, and yield the hash, plan and
+/// encoding of every node stored in `db`. Inline nodes are part of their parent's encoding and are
+/// not yielded separately. Nodes that are missing from `db` or do not decode are skipped along with
+/// their subtrees, so partial tries such as those rebuilt from proofs can be walked.
+pub fn iter_nodes<'db, L>(
+  db: &'db dyn HashDBRef<L::Hash, DBValue>,
+  root: &TrieHash<L>,
+) -> impl Iterator<Item = (TrieHash<L>, NodePlan, Vec<u8>)> + 'db
+where
+  L: TrieLayout + 'db,
+{
+  let mut stack = Vec::new();
+  stack.push(*root);
+  core::iter::from_fn(move || loop {
+    let hash = stack.pop()?;
+    let data = match db.get(&hash, EMPTY_PREFIX) {
+      Some(data) => data,
+      None => continue,
+    };
+    let plan = match <L::Codec as NodeCodec>::decode_plan(&data) {
+      Ok(plan) => plan,
+      Err(_) => continue,
+    };
+    let mut children = Vec::new();
+    child_hashes::<L>(&data, &plan, &mut children);
+    // Push in reverse so children are visited in nibble order.
+    stack.extend(children.into_iter().rev());
+    return Some((hash, plan, data));
+  })
+}
+
+/// The hashes of the children of a node, including those of nodes inlined in it, in nibble order.
+fn child_hashes<L: TrieLayout>(data: &[u8], plan: &NodePlan, hashes: &mut Vec<TrieHash<L>>) {
+  let handles: Vec<&NodeHandlePlan> = match plan {
+    NodePlan::Empty | NodePlan::Leaf { .. } => Vec::new(),
+    NodePlan::Extension { child, .. } => [child].into_iter().collect(),
+    NodePlan::Branch { children, .. } | NodePlan::NibbledBranch { children, .. } => {
+      children.iter().flatten().collect()
+    }
+  };
+  for handle in handles {
+    match handle {
+      NodeHandlePlan::Hash(range) => {
+        if let Some(hash) = decode_hash::<L::Hash>(&data[range.clone()]) {
+          hashes.push(hash);
+        }
+      }
+      NodeHandlePlan::Inline(range) => {
+        let inline = &data[range.clone()];
+        if let Ok(plan) = <L::Codec as NodeCodec>::decode_plan(inline) {
+          child_hashes::<L>(inline, &plan, hashes);
+        }
+      }
+    }
+  }
 }
 
 #[cfg(test)]
@@ -90,7 +865,7 @@ mod tests {
 
     use alloy_rlp::{Encodable, encode_list};
     use revm::primitives::AccountInfo;
-    use trie_db::{TrieLayout, SecTrieDBMut, TrieMut};
+    use trie_db::TrieDBMutBuilder;
     use hash_db::Hasher;
     use ethers::{prelude::*, types::spoof::Account};
 
@@ -115,42 +890,1245 @@ mod tests {
 
       #[test]
       fn it_should_generate_verifiable_proof(){
-        std::env::set_var("RUST_LOG", "trace");
-        pretty_env_logger::init();
         let entries = test_entries();
         let key = entries[0].0.clone();
         let value = entries[0].1.clone();
-        
-        // println!("key 0: {:?}", KeccakHasher::hash(&key));
-        // println!("key 1: {:?}", KeccakHasher::hash(&entries[1].0.clone()));
 
         let (root, proof, item) = test_generate_proof::<EthereumLayout>(entries, key.clone());
         assert!(item.is_some());
-        println!("{:?}", item.unwrap());
-        let test = [0; 32];
-        println!("test: {:?}", test.len());
-        println!("value: {:?}", value);
-        verify_proof::<EthereumLayout>(&root, &proof, &KeccakHasher::hash(&key), Some(&value)).expect("Failed to verify generated proof");
+        verify_proof_hashed_key::<EthereumLayout>(&root, &proof, KeccakHasher::hash(&key).as_slice(), Some(&value)).expect("Failed to verify generated proof");
       }
 
-      fn test_generate_proof<L: TrieLayout>(
-        entries: Vec<(Vec<u8>, Vec<u8>)>,
-        key: Vec<u8>,
-      ) -> (<L::Hash as Hasher>::Out, Vec<Vec<u8>>, Option<Vec<u8>>) {
-        // Populate DB with full trie from entries.
-        
-        let (db, root) = {
-          //let mut db = <MemoryDB<_, HashKey<_>, DBValue>>::default();
-          let mut db = <MemoryDB<_, HashKey<_>, DBValue>>::new(&NULL_NODE);
-          let mut root = Default::default();
-          {
-            let mut trie = <SecTrieDBMut<L>>::new(&mut db, &mut root);
-            for (key, value) in entries.iter() {
-              trie.insert(key, value).unwrap();
-            }
-          }
-          (db, root)
-        };
+      #[test]
+      fn it_should_verify_proof_by_raw_key() {
+        let entries = test_entries();
+        let (root, proof, _) = test_generate_proof::<EthereumLayout>(entries.clone(), entries[0].0.clone());
+
+        verify_proof_raw_key::<EthereumLayout>(&root, &proof, &entries[0].0, Some(&entries[0].1)).expect("Failed to verify proof by raw key");
+        assert!(verify_proof_raw_key::<EthereumLayout>(&root, &proof, KeccakHasher::hash(&entries[0].0).as_slice(), Some(&entries[0].1)).is_err());
+      }
+
+      #[test]
+      fn it_should_verify_proof_by_hashed_key() {
+        let entries = test_entries();
+        let (root, proof, _) = test_generate_proof::<EthereumLayout>(entries.clone(), entries[0].0.clone());
+
+        verify_proof_hashed_key::<EthereumLayout>(&root, &proof, KeccakHasher::hash(&entries[0].0).as_slice(), Some(&entries[0].1)).expect("Failed to verify proof by hashed key");
+        assert!(verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &entries[0].0, Some(&entries[0].1)).is_err());
+
+        #[allow(deprecated)]
+        let result = verify_proof::<EthereumLayout>(&root, &proof, KeccakHasher::hash(&entries[0].0).as_slice(), Some(&entries[0].1));
+        assert_eq!(result, Ok(()));
+      }
+
+      #[test]
+      fn it_should_box_verify_error_as_std_error() {
+        fn verify(root: &B256, proof: &[Vec<u8>], key: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+          verify_proof_raw_key::<EthereumLayout>(root, proof, key, Some(b"not the value"))?;
+          Ok(())
+        }
+
+        let entries = test_entries();
+        let (root, proof, _) = test_generate_proof::<EthereumLayout>(entries.clone(), entries[0].0.clone());
+        let err = verify(&root, &proof, &entries[0].0).unwrap_err();
+        assert!(err.to_string().starts_with("Expected value was not found in the trie"));
+
+        let err: Box<dyn std::error::Error> = Box::new(VerifyError::<B256, rlp::DecoderError>::DecodeError { depth: 1, source: rlp::DecoderError::RlpIsTooShort });
+        assert!(err.source().is_some());
+        assert_eq!(err.to_string(), format!("Unable to decode proof node: {} depth=1", rlp::DecoderError::RlpIsTooShort));
+      }
+
+      #[test]
+      fn it_should_collect_leaves_of_batch_proof() {
+        let mut entries = test_entries();
+        entries.extend(test_entries());
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let proven = &entries[..4];
+        let mut nodes = Vec::new();
+        for (key, _) in proven.iter() {
+          nodes.extend(generate_proof::<EthereumLayout>(&db, &root, key).unwrap().0);
+        }
+
+        let leaves = leaves_in_proof::<EthereumLayout>(&root, &nodes).unwrap();
+        for (key, value) in proven.iter() {
+          let path: Vec<u8> = KeccakHasher::hash(key).iter().flat_map(|byte| [byte >> 4, byte & 0xf]).collect();
+          assert!(leaves.contains(&(path, value.clone())));
+        }
+        let mut sorted = leaves.clone();
+        sorted.sort();
+        assert_eq!(leaves, sorted);
+      }
+
+      #[test]
+      fn it_should_iterate_nodes_depth_first() {
+        let entries = vec![
+          (b"do".to_vec(), b"verb".to_vec()),
+          (b"dog".to_vec(), b"puppy".to_vec()),
+          (b"horse".to_vec(), b"stallion".to_vec()),
+        ];
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+
+        // A root branch with a leaf for each entry.
+        let nodes: Vec<_> = iter_nodes::<EthereumLayout>(&db, &root).collect();
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(nodes[0].0, root);
+        assert!(matches!(nodes[0].1, NodePlan::Branch { .. }));
+        for (hash, plan, data) in nodes.iter().skip(1) {
+          assert_eq!(*hash, KeccakHasher::hash(data));
+          assert!(matches!(plan, NodePlan::Leaf { .. }));
+        }
+      }
+
+      /// A proof of `value` under `depth` zero bytes through a chain of single nibble extension
+      /// nodes, which no canonical trie contains, ending in a leaf.
+      fn extension_chain_proof(depth: usize, value: &[u8]) -> (B256, Vec<Vec<u8>>) {
+        let mut stream = rlp::RlpStream::new_list(2);
+        stream.append(&vec![0x20u8]);
+        stream.append(&value);
+        let mut proof = vec![stream.out().to_vec()];
+        for _ in 0..depth * 2 {
+          let mut stream = rlp::RlpStream::new_list(2);
+          stream.append(&vec![0x10u8]);
+          stream.append(&KeccakHasher::hash(&proof[0]).as_slice());
+          proof.insert(0, stream.out().to_vec());
+        }
+        (KeccakHasher::hash(&proof[0]), proof)
+      }
+
+      #[test]
+      fn it_should_reject_proof_deeper_than_max_depth() {
+        let value = vec![0xab; 40];
+        let (root, proof) = extension_chain_proof(4, &value);
+        verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &[0; 4], Some(&value)).expect("Failed to verify short extension chain");
+
+        let key = [0; MAX_PROOF_DEPTH / 2];
+        let (root, proof) = extension_chain_proof(key.len(), &value);
+        assert_eq!(
+          verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &key, Some(&value)),
+          Err(VerifyError::ProofTooDeep)
+        );
+        assert_eq!(
+          verify_proof_strict::<EthereumLayout>(&root, &proof, &key, Some(&value)),
+          Err(VerifyError::ProofTooDeep)
+        );
+      }
+
+      #[test]
+      fn it_should_walk_each_node_of_multiproof_once() {
+        // A chain of branches whose 16 children all reference the node below would take 16^8
+        // walks to collect if nodes were walked once per reference.
+        let mut stream = rlp::RlpStream::new_list(2);
+        stream.append(&vec![0x20u8]);
+        stream.append(&vec![0xab; 40]);
+        let mut nodes = vec![stream.out().to_vec()];
+        for _ in 0..8 {
+          let child = KeccakHasher::hash(&nodes[0]);
+          let mut stream = rlp::RlpStream::new_list(17);
+          for _ in 0..16 {
+            stream.append(&child.as_slice());
+          }
+          stream.append_empty_data();
+          nodes.insert(0, stream.out().to_vec());
+        }
+        let root = KeccakHasher::hash(&nodes[0]);
+        assert_eq!(
+          leaves_in_proof::<EthereumLayout>(&root, &nodes),
+          Err(VerifyError::NodeReachedTwice(KeccakHasher::hash(&nodes[1])))
+        );
+
+        let (root, proof) = extension_chain_proof(MAX_PROOF_DEPTH / 2, &[0xab; 40]);
+        assert_eq!(leaves_in_proof::<EthereumLayout>(&root, &proof), Err(VerifyError::ProofTooDeep));
+      }
+
+      #[test]
+      fn it_should_limit_proof_depth_with_options() {
+        let value = vec![0xab; 40];
+        let (root, proof) = extension_chain_proof(4, &value);
+        assert_eq!(proof.len(), 9);
+        let options = |max_depth| VerifyOptions { max_depth, ..Default::default() };
+        assert_eq!(verify_proof_with_options::<EthereumLayout>(&root, &proof, &[0; 4], Some(&value), &options(9)), Ok(()));
+        assert_eq!(
+          verify_proof_with_options::<EthereumLayout>(&root, &proof, &[0; 4], Some(&value), &options(8)),
+          Err(VerifyError::ProofTooDeep)
+        );
+
+        // The default limit rejects this chain, a higher one accepts it.
+        let key = [0; MAX_PROOF_DEPTH / 2];
+        let (root, proof) = extension_chain_proof(key.len(), &value);
+        assert_eq!(
+          verify_proof_with_options::<EthereumLayout>(&root, &proof, &key, Some(&value), &VerifyOptions::default()),
+          Err(VerifyError::ProofTooDeep)
+        );
+        assert_eq!(verify_proof_with_options::<EthereumLayout>(&root, &proof, &key, Some(&value), &options(proof.len())), Ok(()));
+      }
+
+      #[test]
+      fn it_should_check_proof_minimality_with_options() {
+        let entries: Vec<_> = (0..16u8).map(|i| (vec![i; 20], vec![i; 40])).collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let key = KeccakHasher::hash(&entries[0].0);
+        let (mut proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[0].0).unwrap();
+        let (other, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[1].0).unwrap();
+        let minimal = VerifyOptions { check_minimal: true, ..Default::default() };
+        assert_eq!(verify_proof_with_options::<EthereumLayout>(&root, &proof, key.as_slice(), Some(&entries[0].1), &minimal), Ok(()));
+
+        proof.push(other.last().unwrap().clone());
+        assert_eq!(
+          verify_proof_with_options::<EthereumLayout>(&root, &proof, key.as_slice(), Some(&entries[0].1), &VerifyOptions::default()),
+          Ok(())
+        );
+        assert_eq!(
+          verify_proof_with_options::<EthereumLayout>(&root, &proof, key.as_slice(), Some(&entries[0].1), &minimal),
+          Err(VerifyError::NonMinimalProof)
+        );
+      }
+
+      #[test]
+      fn it_should_compare_values_alike_with_or_without_constant_time() {
+        let entries: Vec<_> = (0..16u8).map(|i| (vec![i; 20], vec![i; 40])).collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let key = KeccakHasher::hash(&entries[3].0);
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[3].0).unwrap();
+        assert_eq!(VerifyOptions::default().constant_time, cfg!(feature = "ct-eq"));
+
+        for constant_time in [false, true] {
+          let options = VerifyOptions { constant_time, ..Default::default() };
+          let verify = |value: &[u8]| verify_proof_with_options::<EthereumLayout>(&root, &proof, key.as_slice(), Some(value), &options);
+          assert_eq!(verify(&entries[3].1), Ok(()));
+          assert_eq!(verify(&[3; 39]), Err(VerifyError::ValueMismatch(entries[3].1.clone())));
+          let mut last_differs = entries[3].1.clone();
+          last_differs[39] ^= 1;
+          assert_eq!(verify(&last_differs), Err(VerifyError::ValueMismatch(entries[3].1.clone())));
+        }
+      }
+
+      #[test]
+      fn it_should_check_predicate_against_proven_balance() {
+        let account = |balance: u64| types::AccountState {
+          nonce: 0,
+          balance: types::U256::from(balance),
+          storage_hash: EMPTY_TRIE_ROOT,
+          code_hash: B256::repeat_byte(0x22),
+        };
+        let entries: Vec<_> = (1..=16u8)
+          .map(|i| (vec![i; 20], account(i as u64 * 100).rlp_encode()))
+          .collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let threshold = types::U256::from(500);
+        let rich = |value: Option<&[u8]>| {
+          value
+            .and_then(|value| types::AccountState::rlp_decode(value).ok())
+            .map_or(false, |account| account.balance >= threshold)
+        };
+        let verify = |proof: &[Vec<u8>], address: &[u8], predicate: &dyn Fn(Option<&[u8]>) -> bool| {
+          verify_proof_predicate::<EthereumLayout, _>(&root, proof, address, predicate)
+        };
+
+        for (index, (address, _)) in entries.iter().enumerate() {
+          let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, address).unwrap();
+          assert_eq!(verify(&proof, address, &rich), Ok(index >= 4));
+        }
+        let absent = [0xff; 20];
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &absent).unwrap();
+        assert_eq!(verify(&proof, &absent, &rich), Ok(false));
+        assert_eq!(verify(&proof, &absent, &|value| value.is_none()), Ok(true));
+
+        // The predicate is not consulted for a proof that does not verify.
+        let (mut proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[9].0).unwrap();
+        let last = proof.len() - 1;
+        proof[last][4] ^= 1;
+        assert!(verify(&proof, &entries[9].0, &|_| panic!("Unexpected predicate call")).is_err());
+      }
+
+      #[test]
+      fn it_should_verify_proof_from_hex_inputs() {
+        let entries = test_entries();
+        let (root, proof, _) = test_generate_proof::<EthereumLayout>(entries.clone(), entries[0].0.clone());
+        let root_hex = format!("0x{}", hex::encode(root));
+        let proof_hex: Vec<String> = proof.iter().map(|node| format!("0x{}", hex::encode(node))).collect();
+        let proof_hex: Vec<&str> = proof_hex.iter().map(String::as_str).collect();
+        let key_hex = hex::encode(KeccakHasher::hash(&entries[0].0));
+        let value_hex = format!("0x{}", hex::encode(&entries[0].1));
+
+        verify_proof_hex::<EthereumLayout>(&root_hex, &proof_hex, &key_hex, Some(&value_hex)).expect("Failed to verify proof from hex");
+        assert!(matches!(
+          verify_proof_hex::<EthereumLayout>(&root_hex, &proof_hex, &key_hex, Some("0x00")),
+          Err(VerifyError::ValueMismatch(_))
+        ));
+
+        assert_eq!(
+          verify_proof_hex::<EthereumLayout>(&root_hex, &proof_hex, &key_hex[1..], Some(&value_hex)),
+          Err(VerifyError::HexDecode(hex::FromHexError::OddLength))
+        );
+        assert_eq!(
+          verify_proof_hex::<EthereumLayout>(&root_hex, &["0xzz"], &key_hex, Some(&value_hex)),
+          Err(VerifyError::HexDecode(hex::FromHexError::InvalidHexCharacter { c: 'z', index: 0 }))
+        );
+        assert_eq!(
+          verify_proof_hex::<EthereumLayout>(&root_hex[..64], &proof_hex, &key_hex, Some(&value_hex)),
+          Err(VerifyError::HexDecode(hex::FromHexError::InvalidStringLength))
+        );
+      }
+
+      #[test]
+      fn it_should_verify_key_under_root_extension() {
+        // Keys sharing their first 20 bytes, so the root is an extension over 40 nibbles.
+        let entries_with_prefix = |prefix: u8| -> Vec<(Vec<u8>, Vec<u8>)> {
+          (0..3u8).map(|i| {
+            let mut key = vec![prefix; 20];
+            key.push(i << 4);
+            (key, vec![i + 1; 40])
+          }).collect()
+        };
+        let build = |entries: &[(Vec<u8>, Vec<u8>)]| {
+          let mut db = empty_db();
+          let mut root = Default::default();
+          {
+            let mut trie = TrieDBMutBuilder::<EthereumLayout>::new(&mut db, &mut root).build();
+            for (key, value) in entries.iter() {
+              trie.insert(key, value).unwrap();
+            }
+          }
+          (db, root)
+        };
+        let entries = entries_with_prefix(0x5a);
+        let (db, root) = build(&entries);
+        let (proof, value) = generate_proof_for_key::<EthereumLayout>(&db, &root, &entries[1].0).unwrap();
+        assert_eq!(value, Some(entries[1].1.clone()));
+        assert!(matches!(
+          node_codec::decode_node(&proof[0]),
+          Ok(node_codec::OwnedNode::Extension { path, .. }) if path.len() == 40
+        ));
+
+        verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &entries[1].0, Some(&entries[1].1)).expect("Failed to verify key under root extension");
+        verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &[0x5b; 21], None).expect("Failed to verify key diverging in root extension");
+
+        // A root extension over other nibbles is rejected before its partial is consumed.
+        let other_entries = entries_with_prefix(0x5b);
+        let (other_db, other_root) = build(&other_entries);
+        let (other_proof, _) = generate_proof_for_key::<EthereumLayout>(&other_db, &other_root, &other_entries[1].0).unwrap();
+        assert_eq!(
+          verify_proof_hashed_key::<EthereumLayout>(&root, &other_proof, &other_entries[1].0, Some(&other_entries[1].1)),
+          Err(VerifyError::RootMismatch { expected: root, found: KeccakHasher::hash(&other_proof[0]) })
+        );
+        // So is a root node that is the expected value itself.
+        let value_as_root = vec![entries[1].1.clone()];
+        assert_eq!(
+          verify_proof_hashed_key::<EthereumLayout>(&root, &value_as_root, &entries[1].0, Some(&entries[1].1)),
+          Err(VerifyError::RootMismatch { expected: root, found: KeccakHasher::hash(&entries[1].1) })
+        );
+      }
+
+      #[test]
+      fn it_should_not_take_proof_node_as_value() {
+        let entries = test_entries();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[0].0).unwrap();
+        let hashed_key = KeccakHasher::hash(&entries[0].0);
+        let any_key = [0x42; 32];
+
+        // A node that hashes correctly proves nothing by being equal to the expected value.
+        let root_node = &proof[0];
+        assert!(verify_proof_hashed_key::<EthereumLayout>(&root, &[root_node.clone()], &any_key, Some(root_node)).is_err());
+        assert!(eip1186::recursive::process_node::<EthereumLayout, _>(Some(&root), root_node, NibbleSlice::new(&any_key), Some(root_node), &proof[..0], 0).is_err());
+        for node in proof.iter().skip(1) {
+          assert!(verify_proof_hashed_key::<EthereumLayout>(&root, &proof, hashed_key.as_slice(), Some(node)).is_err());
+          assert!(verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &any_key, Some(node)).is_err());
+        }
+      }
+
+      #[test]
+      fn it_should_distinguish_undecodable_node_from_hash_mismatch() {
+        // An extension over no nibbles, referencing a node that is not valid RLP.
+        let garbage = vec![0xc3, 0x01];
+        let extension_to = |child: &[u8]| {
+          let mut stream = rlp::RlpStream::new_list(2);
+          stream.append(&vec![0x00u8]);
+          stream.append(&KeccakHasher::hash(child).as_slice());
+          stream.out().to_vec()
+        };
+        let parent = extension_to(&garbage);
+        let root = KeccakHasher::hash(&parent);
+        let proof = vec![parent.clone(), garbage.clone()];
+        assert!(matches!(
+          verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &[0x12], Some(b"value")),
+          Err(VerifyError::DecodeError { depth: 1, .. })
+        ));
+        assert!(matches!(
+          verify_proof_strict::<EthereumLayout>(&root, &proof, &[0x12], Some(b"value")),
+          Err(VerifyError::DecodeError { depth: 1, .. })
+        ));
+        assert!(matches!(
+          verify_proof_hashed_key::<EthereumLayout>(&KeccakHasher::hash(&garbage), &[garbage], &[0x12], Some(b"value")),
+          Err(VerifyError::DecodeError { depth: 0, .. })
+        ));
+
+        // A valid leaf in place of the referenced node.
+        let mut stream = rlp::RlpStream::new_list(2);
+        stream.append(&vec![0x20u8, 0x12]);
+        stream.append(&b"value".to_vec());
+        let leaf = stream.out().to_vec();
+        let proof = vec![parent, leaf.clone()];
+        assert_eq!(
+          verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &[0x12], Some(b"value")),
+          Err(VerifyError::HashMismatch { hash: KeccakHasher::hash(&leaf), depth: 1 })
+        );
+        let proof = vec![extension_to(&leaf), leaf];
+        verify_proof_hashed_key::<EthereumLayout>(&KeccakHasher::hash(&proof[0]), &proof, &[0x12], Some(b"value")).expect("Failed to verify leaf under extension");
+      }
+
+      #[test]
+      fn it_should_treat_32_byte_leaf_value_as_value() {
+        // The values are hashes of a real node, as a storage slot holding a hash might be, and
+        // must not be followed into that node even when the proof carries it.
+        let mut stream = rlp::RlpStream::new_list(2);
+        stream.append(&hex_prefix::encode(&[0x1; 64], true));
+        stream.append(&vec![0x77; 40]);
+        let decoy = stream.out().to_vec();
+        let value = KeccakHasher::hash(&decoy).to_vec();
+        let entries: Vec<_> = (0..16u8).map(|i| (vec![i; 32], value.clone())).collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+
+        let (proof, found) = generate_proof::<EthereumLayout>(&db, &root, &entries[3].0).unwrap();
+        assert_eq!(found, Some(value.clone()));
+        match node_codec::decode_node(proof.last().unwrap()) {
+          Ok(node_codec::OwnedNode::Leaf { value: leaf_value, .. }) => assert_eq!(leaf_value, value),
+          other => panic!("Expected the proof to end at a leaf, got {:?}", other),
+        }
+        verify_proof_raw_key::<EthereumLayout>(&root, &proof, &entries[3].0, Some(&value)).expect("Failed to verify 32-byte value");
+        verify_proof_strict::<EthereumLayout>(&root, &proof, KeccakHasher::hash(&entries[3].0).as_slice(), Some(&value)).expect("Failed to verify 32-byte value strictly");
+
+        let mut with_decoy = proof.clone();
+        with_decoy.push(decoy);
+        assert!(verify_proof_raw_key::<EthereumLayout>(&root, &with_decoy, &entries[3].0, Some(&[0x77; 40])).is_err());
+      }
+
+      #[test]
+      fn it_should_verify_proofs_of_single_entry_trie() {
+        let entries = vec![(b"only".to_vec(), vec![0x42; 8])];
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, b"only").unwrap();
+        assert_eq!(proof.len(), 1);
+        assert_eq!(KeccakHasher::hash(&proof[0]), root);
+        assert!(matches!(node_codec::decode_node(&proof[0]), Ok(node_codec::OwnedNode::Leaf { .. })));
+
+        verify_proof_raw_key::<EthereumLayout>(&root, &proof, b"only", Some(&entries[0].1)).expect("Failed to verify sole key");
+        verify_proof_strict::<EthereumLayout>(&root, &proof, KeccakHasher::hash(b"only").as_slice(), Some(&entries[0].1)).expect("Failed to verify sole key strictly");
+
+        // Any other key diverges somewhere in the partial path of the root leaf.
+        let (exclusion, _) = generate_proof::<EthereumLayout>(&db, &root, b"other").unwrap();
+        assert_eq!(exclusion, proof);
+        verify_proof_raw_key::<EthereumLayout>(&root, &proof, b"other", None).expect("Failed to verify exclusion of other key");
+        assert!(matches!(
+          verify_proof_raw_key::<EthereumLayout>(&root, &proof, b"other", Some(&entries[0].1)),
+          Err(VerifyError::NonExistingValue(_))
+        ));
+        assert_eq!(
+          verify_proof_raw_key::<EthereumLayout>(&root, &proof, b"only", None),
+          Err(VerifyError::ExistingValue(entries[0].1.clone()))
+        );
+
+        // The root leaf is hashed and compared to the root itself.
+        let mut stream = rlp::RlpStream::new_list(2);
+        stream.append(&hex_prefix::encode(&[0x0; 64], true));
+        stream.append(&entries[0].1);
+        let forged = stream.out().to_vec();
+        assert_eq!(
+          verify_proof_hashed_key::<EthereumLayout>(&root, &[forged.clone()], &[0; 32], Some(&entries[0].1)),
+          Err(VerifyError::RootMismatch { expected: root, found: KeccakHasher::hash(&forged) })
+        );
+      }
+
+      #[test]
+      fn it_should_merge_proofs_of_two_keys() {
+        let entries: Vec<_> = (0..16u8).map(|i| (vec![i; 20], vec![i; 40])).collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (a, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[1].0).unwrap();
+        let (b, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[2].0).unwrap();
+        let key_a = KeccakHasher::hash(&entries[1].0);
+        let key_b = KeccakHasher::hash(&entries[2].0);
+
+        let merged = merge_proofs::<EthereumLayout>(&root, &a, &b, Some((key_a.as_slice(), key_b.as_slice()))).unwrap();
+        assert!(merged.len() < a.len() + b.len());
+        assert_eq!(merged[..a.len()], a[..]);
+        verify_proof_unordered::<EthereumLayout>(&root, &merged, key_a.as_slice(), Some(&entries[1].1)).expect("Failed to verify first key in merged proof");
+        verify_proof_unordered::<EthereumLayout>(&root, &merged, key_b.as_slice(), Some(&entries[2].1)).expect("Failed to verify second key in merged proof");
+
+        let key_c = KeccakHasher::hash(&entries[3].0);
+        assert_eq!(merge_proofs::<EthereumLayout>(&root, &a, &b, None).unwrap(), merged);
+        assert!(merge_proofs::<EthereumLayout>(&root, &a, &b, Some((key_a.as_slice(), key_c.as_slice()))).is_err());
+      }
+
+      #[test]
+      fn it_should_report_root_mismatch_for_wrong_root() {
+        let entries = test_entries();
+        let (root, proof, _) = test_generate_proof::<EthereumLayout>(entries.clone(), entries[0].0.clone());
+        let key = KeccakHasher::hash(&entries[0].0);
+        let wrong_root = B256::repeat_byte(0x11);
+        assert_eq!(
+          verify_proof_hashed_key::<EthereumLayout>(&wrong_root, &proof, key.as_slice(), Some(&entries[0].1)),
+          Err(VerifyError::RootMismatch { expected: wrong_root, found: root })
+        );
+        assert_eq!(
+          verify_proof_raw_key::<EthereumLayout>(&wrong_root, &proof, &entries[0].0, None),
+          Err(VerifyError::RootMismatch { expected: wrong_root, found: root })
+        );
+        // Undecodable root nodes are rejected by their hash first too.
+        assert_eq!(
+          verify_proof_hashed_key::<EthereumLayout>(&root, &[vec![0xc3, 0x01]], key.as_slice(), Some(&entries[0].1)),
+          Err(VerifyError::RootMismatch { expected: root, found: KeccakHasher::hash(&[0xc3, 0x01]) })
+        );
+      }
+
+      #[test]
+      fn it_should_verify_stripped_proof_with_external_value() {
+        let entries: Vec<_> = (0..16u8).map(|i| (vec![i; 20], vec![i; 100])).collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (mut proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[5].0).unwrap();
+        let key = KeccakHasher::hash(&entries[5].0);
+        let full_len: usize = proof.iter().map(Vec::len).sum();
+
+        assert_eq!(strip_leaf_value(&mut proof), Some(entries[5].1.clone()));
+        assert!(proof.iter().map(Vec::len).sum::<usize>() < full_len - 100);
+        assert!(verify_proof_hashed_key::<EthereumLayout>(&root, &proof, key.as_slice(), Some(&entries[5].1)).is_err());
+        verify_proof_with_external_value::<EthereumLayout>(&root, &proof, key.as_slice(), &entries[5].1).expect("Failed to verify stripped proof");
+        assert!(matches!(
+          verify_proof_with_external_value::<EthereumLayout>(&root, &proof, key.as_slice(), &entries[6].1),
+          Err(VerifyError::HashMismatch { depth, .. }) if depth == proof.len() - 1
+        ));
+
+        // A proof ending at a branch is left as it is.
+        let mut truncated = proof[..1].to_vec();
+        assert_eq!(strip_leaf_value(&mut truncated), None);
+        assert_eq!(truncated, proof[..1]);
+      }
+
+      #[test]
+      fn it_should_find_divergence_of_missing_key() {
+        let entries: Vec<_> = (0..16u8).map(|i| (vec![i; 20], vec![i; 40])).collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[3].0).unwrap();
+        assert_eq!(find_divergence::<EthereumLayout>(&root, &proof, &entries[3].0), Ok(None));
+
+        // The trie has a path for a prefix of the missing key as long as some key shares it.
+        let nibbles = |key: &[u8]| -> Vec<u8> {
+          KeccakHasher::hash(key).iter().flat_map(|byte| [byte >> 4, byte & 0x0f]).collect()
+        };
+        let missing = vec![0xff; 20];
+        let missing_nibbles = nibbles(&missing);
+        let diverged_at = entries.iter()
+          .map(|(key, _)| nibbles(key).iter().zip(missing_nibbles.iter()).take_while(|(a, b)| a == b).count())
+          .max()
+          .unwrap();
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &missing).unwrap();
+        assert_eq!(find_divergence::<EthereumLayout>(&root, &proof, &missing), Ok(Some(diverged_at)));
+        assert!(matches!(
+          find_divergence::<EthereumLayout>(&root, &proof[1..], &missing),
+          Err(VerifyError::HashMismatch { depth: 0, .. })
+        ));
+      }
+
+      #[test]
+      fn it_should_report_proof_depth() {
+        let value = vec![0xab; 40];
+        let (root, proof) = extension_chain_proof(4, &value);
+        assert_eq!(proof_depth::<EthereumLayout>(&root, &proof, &[0; 4]), Ok(9));
+        assert_eq!(proof_depth::<EthereumLayout>(&root, &proof, &[0, 0xff, 0, 0]), Ok(3));
+
+        let entries = vec![
+          (vec![0x12, 0x34, 0x56, 0x70], vec![0xaa; 32]),
+          (vec![0x12, 0x34, 0x56, 0x78], vec![0xbb; 32]),
+          (vec![0x19, 0x00, 0x00, 0x00], vec![0xcc; 32]),
+        ];
+        let mut db = empty_db();
+        let mut root = Default::default();
+        {
+          let mut trie = TrieDBMutBuilder::<EthereumLayout>::new(&mut db, &mut root).build();
+          for (key, value) in entries.iter() {
+            trie.insert(key, value).unwrap();
+          }
+        }
+        // Extension, branch, extension, branch and leaf.
+        let (proof, _) = generate_proof_for_key::<EthereumLayout>(&db, &root, &entries[1].0).unwrap();
+        assert_eq!(proof_depth::<EthereumLayout>(&root, &proof, &entries[1].0), Ok(5));
+        // Extension, branch and leaf.
+        let (proof, _) = generate_proof_for_key::<EthereumLayout>(&db, &root, &entries[2].0).unwrap();
+        assert_eq!(proof_depth::<EthereumLayout>(&root, &proof, &entries[2].0), Ok(3));
+
+        let mut tampered = proof.clone();
+        tampered[1][3] ^= 1;
+        assert!(matches!(
+          proof_depth::<EthereumLayout>(&root, &tampered, &entries[2].0),
+          Err(VerifyError::HashMismatch { depth: 1, .. })
+        ));
+      }
+
+      #[test]
+      fn it_should_verify_keys_of_any_nibble_length() {
+        // `[0x01]` and `[0x01, 0x23]` end at the values of branches, the others at leaves.
+        let entries = vec![
+          (vec![0x01], vec![0xaa; 32]),
+          (vec![0x01, 0x23], vec![0xbb; 32]),
+          (vec![0x01, 0x23, 0x45], vec![0xcc; 32]),
+          (vec![0x01, 0x24, 0x00, 0x00, 0x00], vec![0xdd; 32]),
+          (vec![0x02], vec![0xee; 32]),
+        ];
+        let mut db = empty_db();
+        let mut root = Default::default();
+        {
+          let mut trie = TrieDBMutBuilder::<EthereumLayout>::new(&mut db, &mut root).build();
+          for (key, value) in entries.iter() {
+            trie.insert(key, value).unwrap();
+          }
+        }
+
+        for (key, value) in entries.iter() {
+          let (proof, found) = generate_proof_for_key::<EthereumLayout>(&db, &root, key).unwrap();
+          assert_eq!(found.as_ref(), Some(value));
+          verify_proof_hashed_key::<EthereumLayout>(&root, &proof, key, Some(value)).expect("Failed to verify key");
+          assert_eq!(
+            verify_proof_hashed_key::<EthereumLayout>(&root, &proof, key, None),
+            Err(VerifyError::ExistingValue(value.clone()))
+          );
+          assert!(verify_proof_hashed_key::<EthereumLayout>(&root, &proof, key, Some(&[0xff; 32])).is_err());
+        }
+
+        // Keys ending inside an extension or a leaf path, leading to an empty slot of a branch, or
+        // running past a leaf are absent.
+        for key in [vec![], vec![0x00], vec![0x01, 0x24], vec![0x01, 0x23, 0x45, 0x67], vec![0x02, 0x00]] {
+          let (proof, found) = generate_proof_for_key::<EthereumLayout>(&db, &root, &key).unwrap();
+          assert_eq!(found, None);
+          verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &key, None).expect("Failed to verify absent key");
+          assert!(matches!(
+            verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &key, Some(&[0xaa; 32])),
+            Err(VerifyError::NonExistingValue(_))
+          ));
+        }
+      }
+
+      #[test]
+      fn it_should_generate_self_verified_proofs() {
+        let entries: Vec<_> = (0..64u8).map(|i| (vec![i; 20], vec![i; 40])).collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        for (key, value) in entries.iter() {
+          let (proof, item) = generate_proof_verified::<EthereumLayout>(&db, &root, key).unwrap();
+          assert_eq!(item.as_ref(), Some(value));
+          assert_eq!((proof, item), generate_proof::<EthereumLayout>(&db, &root, key).unwrap());
+        }
+        let (_, item) = generate_proof_verified::<EthereumLayout>(&db, &root, b"missing").unwrap();
+        assert_eq!(item, None);
+
+        // A node the proof needs is missing from the database.
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[0].0).unwrap();
+        let partial = build_partial_db(&proof[..proof.len() - 1]);
+        assert!(matches!(
+          generate_proof_verified::<EthereumLayout>(&partial, &root, &entries[0].0),
+          Err(GenerateProofError::Trie(_))
+        ));
+      }
+
+      #[test]
+      fn it_should_reassemble_key_path_of_proven_leaf() {
+        let entries: Vec<_> = (0..64u8).map(|i| (vec![i; 20], vec![i; 40])).collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        for (key, _) in entries.iter() {
+          let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, key).unwrap();
+          assert_eq!(verified_key_path::<EthereumLayout>(&root, &proof, key), Ok(KeccakHasher::hash(key)));
+        }
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, b"missing").unwrap();
+        // An exclusion proof ends at no value, or at the leaf of another key.
+        assert_ne!(verified_key_path::<EthereumLayout>(&root, &proof, b"missing"), Ok(KeccakHasher::hash(b"missing")));
+      }
+
+      #[test]
+      fn it_should_reassemble_different_key_path_from_tampered_partial() {
+        // A root leaf whose partial differs from the hashed key in its last nibble.
+        let key = KeccakHasher::hash(b"key");
+        let mut nibbles = utils::to_nibbles(key.as_slice());
+        nibbles[63] ^= 1;
+        let mut stream = rlp::RlpStream::new_list(2);
+        stream.append(&hex_prefix::encode(&nibbles, true));
+        stream.append(&vec![0xab; 40]);
+        let proof = vec![stream.out().to_vec()];
+        let root = KeccakHasher::hash(&proof[0]);
+
+        let key_path = verified_key_path::<EthereumLayout>(&root, &proof, b"key").unwrap();
+        assert_ne!(key_path, key);
+        assert_eq!(key_path.as_slice(), utils::from_nibbles(&nibbles).unwrap());
+        assert!(verify_proof_raw_key::<EthereumLayout>(&root, &proof, b"key", Some(&[0xab; 40])).is_err());
+
+        let mut tampered = proof.clone();
+        tampered[0][5] ^= 1;
+        assert!(matches!(
+          verified_key_path::<EthereumLayout>(&root, &tampered, b"key"),
+          Err(VerifyError::HashMismatch { depth: 0, .. })
+        ));
+      }
+
+      #[test]
+      fn it_should_stream_proof_nodes_in_order() {
+        let entries: Vec<_> = (0..64u8).map(|i| (vec![i; 20], vec![i; 40])).collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        for key in [entries[5].0.clone(), entries[63].0.clone(), b"missing".to_vec()] {
+          let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &key).unwrap();
+          let streamed: Result<Vec<_>, _> = generate_proof_stream::<EthereumLayout>(&db, &root, &key).collect();
+          assert_eq!(streamed.unwrap(), proof);
+        }
+
+        // A node missing from the database is reported after the nodes before it, and ends the stream.
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[5].0).unwrap();
+        let partial = build_partial_db(&proof[..proof.len() - 1]);
+        let missing = KeccakHasher::hash(&proof[proof.len() - 1]);
+        let streamed: Vec<_> = generate_proof_stream::<EthereumLayout>(&partial, &root, &entries[5].0).collect();
+        assert_eq!(streamed.len(), proof.len());
+        for (streamed, node) in streamed.iter().zip(&proof[..proof.len() - 1]) {
+          assert_eq!(streamed.as_ref().unwrap(), node);
+        }
+        assert!(matches!(streamed.last(), Some(Err(err)) if **err == TrieError::IncompleteDatabase(missing)));
+
+        // So is a missing root.
+        let empty = build_partial_db(&[]);
+        let streamed: Vec<_> = generate_proof_stream::<EthereumLayout>(&empty, &root, &entries[5].0).collect();
+        assert!(matches!(streamed.as_slice(), [Err(err)] if **err == TrieError::InvalidStateRoot(root)));
+      }
+
+      #[test]
+      fn it_should_verify_proof_with_any_root_type() {
+        let entries = test_entries();
+        let (root, proof, _) = test_generate_proof::<EthereumLayout>(entries.clone(), entries[0].0.clone());
+        let key = KeccakHasher::hash(&entries[0].0);
+        let value = Some(entries[0].1.as_slice());
+
+        assert_eq!(verify_proof_root(root, &proof, key.as_slice(), value), Ok(()));
+        assert_eq!(verify_proof_root(&root, &proof, key.as_slice(), value), Ok(()));
+        assert_eq!(verify_proof_root(root.0, &proof, key.as_slice(), value), Ok(()));
+        assert_eq!(verify_proof_root(H256::from(root.0), &proof, key.as_slice(), value), Ok(()));
+      }
+
+      #[test]
+      fn it_should_minimize_multiproof_to_single_key_path() {
+        let entries = test_entries();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (proof0, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[0].0).unwrap();
+        let (proof1, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[1].0).unwrap();
+        let mut nodes = proof1;
+        nodes.extend(proof0.iter().cloned());
+
+        let key = KeccakHasher::hash(&entries[0].0);
+        let minimized = minimize_proof::<EthereumLayout>(&root, &nodes, key.as_slice()).unwrap();
+        assert_eq!(minimized, proof0);
+        verify_proof_hashed_key::<EthereumLayout>(&root, &minimized, key.as_slice(), Some(&entries[0].1)).expect("Failed to verify minimized proof");
+      }
+
+      #[test]
+      fn it_should_verify_converted_proof_with_trie_db() {
+        let entries = test_entries();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+
+        for key in [entries[0].0.clone(), vec![0xff; 20]] {
+          let (proof, value) = generate_proof::<EthereumLayout>(&db, &root, &key).unwrap();
+          let trie_key = KeccakHasher::hash(&key);
+          let compact = to_trie_db_proof::<EthereumLayout>(&proof, trie_key.as_slice()).unwrap();
+          assert_eq!(compact.len(), proof.len());
+
+          let items = [(trie_key.as_slice(), value.as_deref())];
+          trie_db::proof::verify_proof::<TrieDbProofLayout<KeccakHasher>, _, _, _>(&root, &compact, items.iter()).expect("Failed to verify converted proof with trie-db");
+          let upstream = trie_db::proof::generate_proof::<_, TrieDbProofLayout<KeccakHasher>, _, _>(&db, &root, &[trie_key.as_slice()]).unwrap();
+          assert_eq!(compact, upstream);
+
+          // The Ethereum codec reads the omitted child in a branch slot as no child, so it cannot
+          // follow the path of a proven value.
+          if value.is_some() {
+            assert!(trie_db::proof::verify_proof::<EthereumLayout, _, _, _>(&root, &compact, items.iter()).is_err());
+          }
+        }
+      }
+
+      #[test]
+      fn it_should_walk_proofs_as_recursive_baseline() {
+        use eip1186::recursive;
+
+        for _ in 0..20 {
+          let entries: Vec<_> = (0..16).map(|_| random_test_account()).collect();
+          let (db, root) = test_trie::<EthereumLayout>(&entries);
+          let mut keys: Vec<_> = entries.iter().take(4).cloned().collect();
+          keys.push((Address::random().0.to_vec(), entries[0].1.clone()));
+
+          for (key, value) in keys.iter() {
+            let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, key).unwrap();
+            let hashed_key = KeccakHasher::hash(key);
+            let mut wrong_value = value.clone();
+            wrong_value[0] ^= 1;
+            let mut swapped = proof.clone();
+            swapped.swap(0, proof.len() - 1);
+
+            for proof in [&proof, &swapped] {
+              let nodes = index_nodes::<EthereumLayout>(proof);
+              for expected in [Some(value.as_slice()), Some(wrong_value.as_slice()), None] {
+                let walk = |root: Option<&B256>| {
+                  process_node::<EthereumLayout, _>(root, &proof[0], NibbleSlice::new(hashed_key.as_slice()), expected, &proof[1..], 0)
+                };
+                let baseline = |root: Option<&B256>| {
+                  recursive::process_node::<EthereumLayout, _>(root, &proof[0], NibbleSlice::new(hashed_key.as_slice()), expected, &proof[1..], 0)
+                };
+                assert_eq!(walk(None), baseline(None));
+                assert_eq!(walk(Some(&root)), baseline(Some(&root)));
+
+                let root_node = nodes[&root];
+                assert_eq!(
+                  process_node::<EthereumLayout, _>(Some(&root), root_node, NibbleSlice::new(hashed_key.as_slice()), expected, &nodes, 0),
+                  recursive::process_node::<EthereumLayout, _>(Some(&root), root_node, NibbleSlice::new(hashed_key.as_slice()), expected, &nodes, 0)
+                );
+              }
+            }
+          }
+        }
+      }
+
+      #[test]
+      fn it_should_walk_64_deep_path() {
+        use eip1186::recursive;
+
+        // Every other key diverges from `key` at a different nibble, so the path to `key` goes
+        // through a branch at each of its 64 nibbles before its leaf.
+        let key = [0x5au8; 32];
+        let mut entries = vec![(key.to_vec(), vec![0xab; 32])];
+        for index in 0..64 {
+          let mut other = key;
+          other[index / 2] ^= if index % 2 == 0 { 0x10 } else { 0x01 };
+          entries.push((other.to_vec(), vec![index as u8; 32]));
+        }
+        let mut db = empty_db();
+        let mut root = Default::default();
+        {
+          let mut trie = TrieDBMutBuilder::<EthereumLayout>::new(&mut db, &mut root).build();
+          for (key, value) in entries.iter() {
+            trie.insert(key, value).unwrap();
+          }
+        }
+        let (proof, _) = generate_proof_for_key::<EthereumLayout>(&db, &root, &key).unwrap();
+        assert_eq!(proof.len(), 65);
+        assert_eq!(proof_depth::<EthereumLayout>(&root, &proof, &key), Ok(65));
+
+        let wrong_value = [0xcd; 32];
+        for expected in [Some(&entries[0].1[..]), Some(&wrong_value[..]), None] {
+          assert_eq!(
+            process_node::<EthereumLayout, _>(Some(&root), &proof[0], NibbleSlice::new(&key), expected, &proof[1..], 0),
+            recursive::process_node::<EthereumLayout, _>(Some(&root), &proof[0], NibbleSlice::new(&key), expected, &proof[1..], 0)
+          );
+        }
+        verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &key, Some(&entries[0].1)).expect("Failed to verify 64-deep proof");
+      }
+
+      #[test]
+      fn it_should_canonicalize_proof_nodes() {
+        let entries = test_entries();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[0].0).unwrap();
+        let mut shuffled: Vec<_> = proof.iter().rev().cloned().collect();
+        shuffled.push(proof[1].clone());
+
+        let canonical = canonicalize_proof(&proof);
+        assert_eq!(canonicalize_proof(&shuffled), canonical);
+        assert_eq!(canonical.len(), proof.len());
+        let hashes: Vec<_> = canonical.iter().map(|node| KeccakHasher::hash(node)).collect();
+        assert!(hashes.windows(2).all(|pair| pair[0] < pair[1]));
+      }
+
+      #[test]
+      fn it_should_detect_extraneous_proof_nodes() {
+        let entries = test_entries();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (proof0, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[0].0).unwrap();
+        let (proof1, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[1].0).unwrap();
+        let key = KeccakHasher::hash(&entries[0].0);
+        assert_eq!(is_minimal_proof::<EthereumLayout>(&root, &proof0, key.as_slice()), Ok(true));
+
+        let unrelated = proof1.iter().find(|node| !proof0.contains(node)).unwrap();
+        let mut proof = proof0.clone();
+        proof.push(unrelated.clone());
+        assert_eq!(is_minimal_proof::<EthereumLayout>(&root, &proof, key.as_slice()), Ok(false));
+
+        let mut proof = proof0.clone();
+        proof.push(proof0[0].clone());
+        assert_eq!(is_minimal_proof::<EthereumLayout>(&root, &proof, key.as_slice()), Ok(false));
+
+        assert_eq!(
+          is_minimal_proof::<EthereumLayout>(&root, &proof0[..1], key.as_slice()),
+          Err(VerifyError::IncompleteProof)
+        );
+      }
+
+      #[test]
+      fn it_should_start_empty_db_at_layout_empty_root() {
+        let mut db = empty_db_for::<EthereumLayout>();
+        let mut root = Default::default();
+        TrieDBMutBuilder::<EthereumLayout>::new(&mut db, &mut root).build().commit();
+        assert_eq!(root, <EthereumLayout as TrieLayout>::Codec::hashed_null_node());
+        assert_eq!(root, EMPTY_TRIE_ROOT);
+        assert_eq!(HashDB::get(&db, &root, EMPTY_PREFIX), Some(node_codec::NULL_NODE.to_vec()));
+
+        let trie = TrieDBBuilder::<EthereumLayout>::new(&db, &root).build();
+        assert_eq!(trie.get(&[0x01]).unwrap(), None);
+      }
+
+      #[test]
+      fn it_should_find_orphan_nodes_in_partial_db() {
+        let entries = test_entries();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let mut nodes = Vec::new();
+        for (key, _) in entries[..2].iter() {
+          nodes.extend(generate_proof::<EthereumLayout>(&db, &root, key).unwrap().0);
+        }
+
+        let mut partial = build_partial_db(&nodes);
+        {
+          let trie = TrieDBBuilder::<EthereumLayout>::new(&partial, &root).build();
+          let key = KeccakHasher::hash(&entries[0].0);
+          assert_eq!(trie.get(key.as_slice()).unwrap(), Some(entries[0].1.clone()));
+        }
+        assert_eq!(find_orphan_nodes::<EthereumLayout>(&partial, &root), Vec::new());
+
+        let (_, other_proof, _) = test_generate_proof::<EthereumLayout>(test_entries(), entries[0].0.clone());
+        let spurious = partial.insert(EMPTY_PREFIX, &other_proof[0]);
+        assert_eq!(find_orphan_nodes::<EthereumLayout>(&partial, &root), vec![spurious]);
+      }
+
+      #[test]
+      fn it_should_insert_proof_nodes_under_their_hash() {
+        let entries = test_entries();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[1].0).unwrap();
+
+        let mut partial = empty_db();
+        insert_proof_nodes(&mut partial, &proof);
+        for node in proof.iter() {
+          assert_eq!(HashDB::get(&partial, &KeccakHasher::hash(node), EMPTY_PREFIX), Some(node.clone()));
+        }
+        let trie = TrieDBBuilder::<EthereumLayout>::new(&partial, &root).build();
+        let key = KeccakHasher::hash(&entries[1].0);
+        assert_eq!(trie.get(key.as_slice()).unwrap(), Some(entries[1].1.clone()));
+        // The proof of one key does not cover another.
+        let other = KeccakHasher::hash(&entries[0].0);
+        assert!(trie.get(other.as_slice()).is_err());
+      }
+
+      #[test]
+      fn it_should_reject_shuffled_proof_nodes() {
+        let entries = test_entries();
+        let key = entries[0].0.clone();
+        let (root, mut proof, _) = test_generate_proof::<EthereumLayout>(entries.clone(), key.clone());
+        assert!(proof.len() > 1);
+        proof.reverse();
+
+        let hashed_key = KeccakHasher::hash(&key);
+        let result = verify_proof_hashed_key::<EthereumLayout>(&root, &proof, hashed_key.as_slice(), Some(&entries[0].1));
+        assert_eq!(result, Err(VerifyError::NodesOutOfOrder));
+      }
+
+      #[test]
+      fn it_should_verify_proof_strict() {
+        let entries = test_entries();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[0].0).unwrap();
+        let hashed_key = KeccakHasher::hash(&entries[0].0);
+        verify_proof_strict::<EthereumLayout>(&root, &proof, hashed_key.as_slice(), Some(&entries[0].1)).expect("Failed to verify proof strictly");
+
+        let (proof, item) = generate_proof::<EthereumLayout>(&db, &root, &[0xff; 20]).unwrap();
+        assert!(item.is_none());
+        let missing_key = KeccakHasher::hash(&[0xff; 20]);
+        verify_proof_strict::<EthereumLayout>(&root, &proof, missing_key.as_slice(), None).expect("Failed to verify exclusion proof strictly");
+      }
+
+      #[test]
+      fn it_should_report_hash_mismatch_for_tampered_interior_node() {
+        // The hashed keys of [0] and [9] share their first nibble, so the proof of [0] runs through
+        // an interior branch node at depth 1.
+        let entries = vec![
+          (vec![0], b"zero".to_vec()),
+          (vec![9], b"nine".to_vec()),
+          (vec![1], b"one".to_vec()),
+        ];
+        let (root, mut proof, _) = test_generate_proof::<EthereumLayout>(entries.clone(), vec![0]);
+        assert_eq!(proof.len(), 3);
+        proof[1][5] ^= 0x01;
+
+        let hashed_key = KeccakHasher::hash(&[0]);
+        let result = verify_proof_strict::<EthereumLayout>(&root, &proof, hashed_key.as_slice(), Some(b"zero"));
+        assert!(matches!(result, Err(VerifyError::HashMismatch { depth: 1, .. })));
+      }
+
+      #[test]
+      fn it_should_verify_proof_structure_without_value() {
+        let entries = vec![
+          (vec![0], b"zero".to_vec()),
+          (vec![9], b"nine".to_vec()),
+          (vec![1], b"one".to_vec()),
+        ];
+        let (root, mut proof, _) = test_generate_proof::<EthereumLayout>(entries.clone(), vec![0]);
+        let hashed_key = KeccakHasher::hash(&[0]);
+        verify_proof_structure::<EthereumLayout>(&root, &proof, hashed_key.as_slice()).expect("Failed to verify proof structure");
+
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (exclusion, _) = generate_proof::<EthereumLayout>(&db, &root, &[5]).unwrap();
+        let missing_key = KeccakHasher::hash(&[5]);
+        verify_proof_structure::<EthereumLayout>(&root, &exclusion, missing_key.as_slice()).expect("Failed to verify exclusion proof structure");
+
+        proof[1][5] ^= 0x01;
+        assert!(matches!(
+          verify_proof_structure::<EthereumLayout>(&root, &proof, hashed_key.as_slice()),
+          Err(VerifyError::HashMismatch { depth: 1, .. })
+        ));
+      }
+
+      #[test]
+      fn it_should_return_audited_value_and_node_hashes() {
+        let entries = test_entries();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[0].0).unwrap();
+        let key = KeccakHasher::hash(&entries[0].0);
+
+        let (value, hashes) = verify_proof_audited::<EthereumLayout>(&root, &proof, key.as_slice(), Some(&entries[0].1)).unwrap();
+        assert_eq!(value, Some(entries[0].1.clone()));
+        assert_eq!(hashes[0], root);
+        assert_eq!(Ok(hashes.len()), proof_depth::<EthereumLayout>(&root, &proof, key.as_slice()));
+        let expected: Vec<_> = proof.iter().map(|node| KeccakHasher::hash(node)).collect();
+        assert_eq!(hashes, expected);
+
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &[0xff; 20]).unwrap();
+        let key = KeccakHasher::hash(&[0xff; 20]);
+        let (value, hashes) = verify_proof_audited::<EthereumLayout>(&root, &proof, key.as_slice(), None).unwrap();
+        assert_eq!(value, None);
+        assert_eq!(hashes.len(), proof.len());
+      }
+
+      #[test]
+      fn it_should_verify_key_through_inline_nodes() {
+        // With short raw keys and values every node but the root is under 32 bytes, so the branch
+        // and its leaves are all embedded in the root extension node.
+        let entries = vec![
+          (vec![0x01], b"a".to_vec()),
+          (vec![0x02], b"b".to_vec()),
+          (vec![0x03], b"c".to_vec()),
+        ];
+        let mut db = empty_db();
+        let mut root = Default::default();
+        {
+          let mut trie = TrieDBMutBuilder::<EthereumLayout>::new(&mut db, &mut root).build();
+          for (key, value) in entries.iter() {
+            trie.insert(key, value).unwrap();
+          }
+        }
+
+        let mut recorder = Recorder::<EthereumLayout>::new();
+        let item = {
+          let trie = TrieDBBuilder::<EthereumLayout>::new(&db, &root).with_recorder(&mut recorder).build();
+          trie.get(&[0x02]).unwrap()
+        };
+        let proof: Vec<Vec<u8>> = recorder.drain().into_iter().map(|r| r.data).collect();
+        assert_eq!(item, Some(b"b".to_vec()));
+        assert_eq!(proof.len(), 1);
+
+        verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &[0x02], Some(b"b")).expect("Failed to verify proof through inline nodes");
+        verify_proof_strict::<EthereumLayout>(&root, &proof, &[0x02], Some(b"b")).expect("Failed to strictly verify proof through inline nodes");
+        verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &[0x04], None).expect("Failed to verify exclusion through inline nodes");
+        assert_eq!(
+          verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &[0x02], Some(b"a")),
+          Err(VerifyError::ValueMismatch(b"b".to_vec()))
+        );
+      }
+
+      #[test]
+      fn it_should_verify_proof_through_odd_and_even_extensions() {
+        let cases = [
+          // An extension of one nibble at the root and of five below it.
+          (
+            vec![
+              (vec![0x12, 0x34, 0x56, 0x70], vec![0xaa; 32]),
+              (vec![0x12, 0x34, 0x56, 0x78], vec![0xbb; 32]),
+              (vec![0x19, 0x00, 0x00, 0x00], vec![0xcc; 32]),
+            ],
+            "d7e6eba5cfbe2ad3b8d388f10041918836a3a592042ec9a7054d4af112f58ef8",
+            vec![0x12, 0x35, 0x55, 0x55],
+          ),
+          // An extension of four nibbles at the root.
+          (
+            vec![
+              (vec![0xab, 0xcd, 0x10], vec![0xaa; 32]),
+              (vec![0xab, 0xcd, 0x20], vec![0xbb; 32]),
+            ],
+            "7466ca364224e636dd8ed4bc6bbb7444a8327cbf5f65c1dc401c4e5df36c9a2a",
+            vec![0xab, 0xce, 0x10],
+          ),
+        ];
+
+        for (entries, expected_root, diverging_key) in cases.iter() {
+          let mut db = empty_db();
+          let mut root = Default::default();
+          {
+            let mut trie = TrieDBMutBuilder::<EthereumLayout>::new(&mut db, &mut root).build();
+            for (key, value) in entries.iter() {
+              trie.insert(key, value).unwrap();
+            }
+          }
+          assert_eq!(root, expected_root.parse::<B256>().unwrap());
+
+          let keys = entries.iter().map(|(key, value)| (key, Some(value.as_slice())));
+          for (key, value) in keys.chain([(diverging_key, None)]) {
+            let (proof, item) = generate_proof_for_key::<EthereumLayout>(&db, &root, key).unwrap();
+            assert_eq!(item.as_deref(), value);
+
+            verify_proof_hashed_key::<EthereumLayout>(&root, &proof, key, value).expect("Failed to verify proof through extensions");
+            verify_proof_strict::<EthereumLayout>(&root, &proof, key, value).expect("Failed to strictly verify proof through extensions");
+            if value.is_none() {
+              assert!(matches!(
+                verify_proof_hashed_key::<EthereumLayout>(&root, &proof, key, Some(&[0xaa; 32])),
+                Err(VerifyError::NonExistingValue(_))
+              ));
+            }
+          }
+        }
+      }
+
+      #[test]
+      fn it_should_generate_proof_for_unhashed_key() {
+        let entries = test_entries();
+        let mut db = empty_db();
+        let mut root = Default::default();
+        {
+          let mut trie = TrieDBMutBuilder::<EthereumLayout>::new(&mut db, &mut root).build();
+          for (key, value) in entries.iter() {
+            trie.insert(key, value).unwrap();
+          }
+        }
+
+        let (key, value) = &entries[0];
+        let (proof, item) = generate_proof_for_key::<EthereumLayout>(&db, &root, key).unwrap();
+        assert_eq!(item.as_ref(), Some(value));
+        verify_proof_hashed_key::<EthereumLayout>(&root, &proof, key, Some(value)).expect("Failed to verify proof for unhashed key");
+
+        let (_, item) = generate_proof::<EthereumLayout>(&db, &root, key).unwrap();
+        assert_eq!(item, None);
+      }
+
+      #[test]
+      fn it_should_verify_proof_against_matching_candidate_root() {
+        let entries = test_entries();
+        let (root, proof, _) = test_generate_proof::<EthereumLayout>(entries.clone(), entries[0].0.clone());
+        let (other_root_a, _, _) = test_generate_proof::<EthereumLayout>(test_entries(), entries[0].0.clone());
+        let (other_root_b, _, _) = test_generate_proof::<EthereumLayout>(test_entries(), entries[0].0.clone());
+        let hashed_key = KeccakHasher::hash(&entries[0].0);
+
+        let roots = [other_root_a, root, other_root_b];
+        assert_eq!(verify_proof_any::<EthereumLayout>(&roots, &proof, hashed_key.as_slice(), Some(&entries[0].1)), Ok(1));
+
+        let roots = [other_root_a, other_root_b];
+        assert_eq!(
+          verify_proof_any::<EthereumLayout>(&roots, &proof, hashed_key.as_slice(), Some(&entries[0].1)),
+          Err(VerifyError::NoMatchingRoot)
+        );
+      }
+
+      #[test]
+      fn it_should_compute_empty_trie_root() {
+        assert_eq!(compute_state_root::<EthereumLayout>(&[]), EMPTY_TRIE_ROOT);
+      }
+
+      #[test]
+      fn it_should_compute_same_root_as_trie() {
+        let entries = test_entries();
+        let (_, root) = test_trie::<EthereumLayout>(&entries);
+        assert_eq!(compute_state_root::<EthereumLayout>(&entries), root);
+      }
+
+      #[test]
+      fn it_should_return_root_after_each_insertion() {
+        let entries = test_entries();
+        let roots = root_after_each::<EthereumLayout>(&entries);
+        assert_eq!(roots.len(), entries.len());
+        for (index, (key, root)) in roots.iter().enumerate() {
+          assert_eq!(key, &entries[index].0);
+          assert_eq!(*root, compute_state_root::<EthereumLayout>(&entries[..=index]));
+        }
+        assert_eq!(roots[2].1, compute_state_root::<EthereumLayout>(&entries));
+      }
+
+      #[test]
+      fn it_should_verify_reversed_proof_unordered() {
+        let entries = test_entries();
+        let key = entries[0].0.clone();
+        let (root, mut proof, _) = test_generate_proof::<EthereumLayout>(entries.clone(), key.clone());
+        proof.reverse();
+
+        verify_proof_unordered::<EthereumLayout>(&root, &proof, KeccakHasher::hash(&key).as_slice(), Some(&entries[0].1)).expect("Failed to verify reversed proof");
+      }
+
+      #[test]
+      fn it_should_verify_proof_with_duplicated_nodes_unordered() {
+        let entries = test_entries();
+        let key = entries[0].0.clone();
+        let (root, proof, _) = test_generate_proof::<EthereumLayout>(entries.clone(), key.clone());
+        let duplicated: Vec<Vec<u8>> = proof.iter().chain(proof.iter()).cloned().collect();
+
+        verify_proof_unordered::<EthereumLayout>(&root, &duplicated, KeccakHasher::hash(&key).as_slice(), Some(&entries[0].1)).expect("Failed to verify proof with duplicated nodes");
+      }
+
+      #[test]
+      fn it_should_verify_proof_from_nodes_with_root_in_the_middle() {
+        let entries = test_entries();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (proof0, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[0].0).unwrap();
+        let (proof1, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[1].0).unwrap();
+        let mut nodes: Vec<Vec<u8>> = proof1[1..].to_vec();
+        nodes.push(proof0[0].clone());
+        nodes.extend(proof0[1..].iter().rev().cloned());
+        assert_ne!(KeccakHasher::hash(&nodes[0]), root);
+
+        let key = KeccakHasher::hash(&entries[0].0);
+        verify_proof_from_nodes::<EthereumLayout>(&root, &nodes, key.as_slice(), Some(&entries[0].1)).expect("Failed to verify proof from unordered nodes");
+        assert_eq!(
+          verify_proof_from_nodes::<EthereumLayout>(&KeccakHasher::hash(b"other root"), &nodes, key.as_slice(), Some(&entries[0].1)),
+          Err(VerifyError::NoMatchingRoot)
+        );
+      }
+
+      #[test]
+      fn it_should_generate_identical_sorted_proofs_from_differently_built_dbs() {
+        let entries = test_entries();
+        let reversed: Vec<_> = entries.iter().rev().cloned().collect();
+        let (db_a, root_a) = test_trie::<EthereumLayout>(&entries);
+        let (db_b, root_b) = test_trie::<EthereumLayout>(&reversed);
+        assert_eq!(root_a, root_b);
+
+        let key = &entries[0].0;
+        let (proof_a, _) = generate_proof_sorted::<EthereumLayout>(&db_a, &root_a, key).unwrap();
+        let (proof_b, _) = generate_proof_sorted::<EthereumLayout>(&db_b, &root_b, key).unwrap();
+        assert_eq!(proof_a, proof_b);
+        verify_proof_unordered::<EthereumLayout>(&root_a, &proof_a, KeccakHasher::hash(key).as_slice(), Some(&entries[0].1)).expect("Failed to verify sorted proof");
+      }
+
+      pub(crate) fn test_trie<L: TrieLayout>(
+        entries: &[(Vec<u8>, Vec<u8>)],
+      ) -> (MemoryDB<L::Hash, HashKey<L::Hash>, DBValue>, <L::Hash as Hasher>::Out) {
+        // Populate DB with full trie from entries.
+        //let mut db = <MemoryDB<_, HashKey<_>, DBValue>>::default();
+        let mut db = empty_db_for::<L>();
+        let mut root = Default::default();
+        {
+          let mut trie = <SecTrieDBMut<L>>::new(&mut db, &mut root);
+          for (key, value) in entries.iter() {
+            trie.insert(key, value).unwrap();
+          }
+        }
+        (db, root)
+      }
+
+      fn test_generate_proof<L: TrieLayout>(
+        entries: Vec<(Vec<u8>, Vec<u8>)>,
+        key: Vec<u8>,
+      ) -> (<L::Hash as Hasher>::Out, Vec<Vec<u8>>, Option<Vec<u8>>) {
+        let (db, root) = test_trie::<L>(&entries);
         // Generate proof for the given keys..
         let (proof, item) = generate_proof::<L>(&db, &root, &key).unwrap();
         (root, proof, item)
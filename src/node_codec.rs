@@ -28,8 +28,8 @@ mod rstd {
 use rlp::{DecoderError, Prototype, Rlp, RlpStream};
 
 use trie_db::{
-    node::{NibbleSlicePlan, NodeHandlePlan, NodePlan, Value, ValuePlan},
-    ChildReference, NodeCodec,
+    node::{NibbleSlicePlan, Node, NodeHandle, NodeHandlePlan, NodePlan, Value, ValuePlan},
+    ChildReference, NibbleSlice, NodeCodec,
 };
 use log::trace;
 
@@ -54,6 +54,8 @@ impl<H: Hasher> NodeCodec for RlpNodeCodec<H>
     }
 
     fn decode_plan(data: &[u8]) -> Result<NodePlan, Self::Error> {
+      #[cfg(feature = "tracing")]
+      let _span = tracing::trace_span!("decode_plan", len = data.len()).entered();
       if data == &HASHED_NULL_NODE {
         // early return if this is == keccak(rlp(null)), aka empty trie root
         // source: https://ethereum.github.io/execution-specs/diffs/frontier_homestead/trie/index.html#empty-trie-root
@@ -62,7 +64,7 @@ impl<H: Hasher> NodeCodec for RlpNodeCodec<H>
 
     let r = Rlp::new(data);
    
-    match r.prototype()? {
+    let plan = match r.prototype()? {
         // either leaf or extension - decode first item with NibbleSlice::???
         // and use is_leaf return to figure out which.
         // if leaf, second item is a value (is_data())
@@ -70,33 +72,36 @@ impl<H: Hasher> NodeCodec for RlpNodeCodec<H>
         // fed back into this function or inline RLP which can be fed back into this function).
         Prototype::List(2) => {
             let (rlp, offset) = r.at_with_offset(0)?;
-            let (data, i) = (rlp.data()?, rlp.payload_info()?);
-            trace!("Decoding rlp partial: {:?}", data);
+            let (partial, i) = (rlp.data()?, rlp.payload_info()?);
+            trace!("Decoding rlp partial: {:?}", partial);
+            // The compact encoded partial always has at least the flags byte.
+            let flags = *partial.first().ok_or(DecoderError::RlpIsTooShort)?;
             let node_plan = match (
                 NibbleSlicePlan::new(
-                    (offset + i.header_len)..(offset + i.header_len + i.value_len),
-                    if data[0] & 16 == 16 { 1 } else { 2 },
+                    checked_range(
+                        (offset + i.header_len)..(offset + i.header_len + i.value_len),
+                        data.len(),
+                    )?,
+                    if flags & 16 == 16 { 1 } else { 2 },
                 ),
-                data[0] & 32 == 32,
+                flags & 32 == 32,
             ) {
                 (slice, true) => Ok(NodePlan::Leaf {
                     partial: slice,
                     value: {
                         let (item, offset) = r.at_with_offset(1)?;
                         let i = item.payload_info()?;
-                        ValuePlan::Inline(
+                        ValuePlan::Inline(checked_range(
                             (offset + i.header_len)..(offset + i.header_len + i.value_len),
-                        )
+                            data.len(),
+                        )?)
                     },
                 }),
                 (slice, false) => Ok(NodePlan::Extension {
                     partial: slice,
                     child: {
                         let (item, offset) = r.at_with_offset(1)?;
-                        let i = item.payload_info()?;
-                        NodeHandlePlan::Hash(
-                            (offset + i.header_len)..(offset + i.header_len + i.value_len),
-                        )
+                        child_plan(&item, offset, data.len())?
                     },
                 }),
             };
@@ -113,13 +118,10 @@ impl<H: Hasher> NodeCodec for RlpNodeCodec<H>
 
             for index in 0..16 {
                 let (item, offset) = r.at_with_offset(index)?;
-                let i = item.payload_info()?;
                 if item.is_empty() {
                     nodes[index] = None;
                 } else {
-                    nodes[index] = Some(NodeHandlePlan::Hash(
-                        (offset + i.header_len)..(offset + i.header_len + i.value_len),
-                    ));
+                    nodes[index] = Some(child_plan(&item, offset, data.len())?);
                 }
             }
 
@@ -131,9 +133,10 @@ impl<H: Hasher> NodeCodec for RlpNodeCodec<H>
                     if item.is_empty() {
                         None
                     } else {
-                        Some(ValuePlan::Inline(
+                        Some(ValuePlan::Inline(checked_range(
                             (offset + i.header_len)..(offset + i.header_len + i.value_len),
-                        ))
+                            data.len(),
+                        )?))
                     }
                 },
             })
@@ -142,7 +145,12 @@ impl<H: Hasher> NodeCodec for RlpNodeCodec<H>
         Prototype::Data(0) => Ok(NodePlan::Empty),
         // something went wrong.
         _ => Err(DecoderError::Custom("Rlp is not valid.")),
+      };
+      #[cfg(feature = "tracing")]
+      if let Ok(plan) = &plan {
+        tracing::trace!(node = plan_kind(plan), "decoded node");
       }
+      plan
     }
   
     fn is_empty_node(data: &[u8]) -> bool {
@@ -156,15 +164,17 @@ impl<H: Hasher> NodeCodec for RlpNodeCodec<H>
 
   fn leaf_node(
       partial: impl Iterator<Item = u8>,
-      _number_nibble: usize,
+      number_nibble: usize,
       value: Value,
   ) -> Vec<u8> {
       let mut stream = RlpStream::new_list(2);
       let partial = partial.collect::<Vec<_>>();
-      trace!("Encoding leaf node, partial: {:?}, nibble: {:?}", partial, _number_nibble);
-      let encoded_partial = compact_encode_leaf(partial);
+      trace!("Encoding leaf node, partial: {:?}, nibble: {:?}", partial, number_nibble);
+      let encoded_partial = compact_encode_leaf(partial, number_nibble);
       stream.append(&encoded_partial);
   
+      // Values are always inline: `Value::Node` only arises for layouts with a
+      // `MAX_INLINE_VALUE`, which `RlpTrieLayout` does not set.
       let value = match value {
           Value::Node(bytes) => bytes,
           Value::Inline(bytes) => bytes,
@@ -175,14 +185,14 @@ impl<H: Hasher> NodeCodec for RlpNodeCodec<H>
   
   fn extension_node(
       partial: impl Iterator<Item = u8>,
-      _number_nibble: usize,
+      number_nibble: usize,
       child_ref: ChildReference<Self::HashOut>,
   ) -> Vec<u8> {
       let mut stream = RlpStream::new_list(2);
       let partial = partial.collect::<Vec<_>>();
-      trace!("Encoding extension node, partial: {:?}, nibble: {:?}", partial, _number_nibble);
+      trace!("Encoding extension node, partial: {:?}, nibble: {:?}", partial, number_nibble);
   
-      let key = compact_encode_extension(partial);
+      let key = compact_encode_extension(partial, number_nibble);
       stream.append(&key);
   
       match child_ref {
@@ -214,6 +224,7 @@ impl<H: Hasher> NodeCodec for RlpNodeCodec<H>
             };
         }
         if let Some(value) = value {
+            // Inline whatever its size, as in `leaf_node`.
             let value = match value {
                 Value::Node(bytes) => bytes,
                 Value::Inline(bytes) => bytes,
@@ -235,11 +246,294 @@ impl<H: Hasher> NodeCodec for RlpNodeCodec<H>
     }
 }
 
-fn compact_encode_leaf(partial: Vec<u8>) -> Vec<u8> {
+/// The codec of [`crate::TrieDbProofLayout`], which is [`RlpNodeCodec`] but for the child a
+/// `trie_db::proof` compact proof omits. trie-db passes that child as an empty inline child, which
+/// this codec encodes as the empty RLP list and plans back from it. The empty list is never a
+/// child in the Ethereum trie, so [`RlpNodeCodec`] leaves it undecodable.
+#[derive(Default, Clone)]
+pub struct TrieDbProofCodec<H>(PhantomData<H>);
+
+/// The RLP of the empty list, which stands for an omitted child in a compact proof.
+const OMITTED_CHILD: u8 = 0xc0;
+
+impl<H: Hasher> NodeCodec for TrieDbProofCodec<H> {
+    type Error = DecoderError;
+    type HashOut = H::Out;
+
+    fn hashed_null_node() -> H::Out {
+        RlpNodeCodec::<H>::hashed_null_node()
+    }
+
+    fn decode_plan(data: &[u8]) -> Result<NodePlan, Self::Error> {
+        let omit = |child: NodeHandlePlan| match child {
+            NodeHandlePlan::Inline(range) if data[range.clone()] == [OMITTED_CHILD] => {
+                NodeHandlePlan::Inline(range.start..range.start)
+            }
+            child => child,
+        };
+        Ok(match RlpNodeCodec::<H>::decode_plan(data)? {
+            NodePlan::Extension { partial, child } => NodePlan::Extension {
+                partial,
+                child: omit(child),
+            },
+            // `RlpNodeCodec` plans the empty list in a branch slot as no child, as `Rlp::is_empty`
+            // holds for it, so the omitted children of a branch are planned here from its items.
+            NodePlan::Branch {
+                mut children,
+                value,
+            } => {
+                let r = Rlp::new(data);
+                for (index, child) in children.iter_mut().enumerate() {
+                    let (item, offset) = r.at_with_offset(index)?;
+                    if item.as_raw() == [OMITTED_CHILD] {
+                        *child = Some(NodeHandlePlan::Inline(offset..offset));
+                    }
+                }
+                NodePlan::Branch { children, value }
+            }
+            plan => plan,
+        })
+    }
+
+    fn is_empty_node(data: &[u8]) -> bool {
+        RlpNodeCodec::<H>::is_empty_node(data)
+    }
+
+    fn empty_node() -> &'static [u8] {
+        RlpNodeCodec::<H>::empty_node()
+    }
+
+    fn leaf_node(partial: impl Iterator<Item = u8>, number_nibble: usize, value: Value) -> Vec<u8> {
+        RlpNodeCodec::<H>::leaf_node(partial, number_nibble, value)
+    }
+
+    fn extension_node(
+        partial: impl Iterator<Item = u8>,
+        number_nibble: usize,
+        child_ref: ChildReference<Self::HashOut>,
+    ) -> Vec<u8> {
+        RlpNodeCodec::<H>::extension_node(partial, number_nibble, omitted_as_empty_list(child_ref))
+    }
+
+    fn branch_node(
+        children: impl Iterator<Item = impl Borrow<Option<ChildReference<Self::HashOut>>>>,
+        value: Option<Value>,
+    ) -> Vec<u8> {
+        let children = children.map(|child| {
+            let child: &Option<ChildReference<H::Out>> = child.borrow();
+            child.clone().map(omitted_as_empty_list)
+        });
+        RlpNodeCodec::<H>::branch_node(children, value)
+    }
+
+    fn branch_node_nibbled(
+        partial: impl Iterator<Item = u8>,
+        number_nibble: usize,
+        children: impl Iterator<Item = impl Borrow<Option<ChildReference<Self::HashOut>>>>,
+        value: Option<Value>,
+    ) -> Vec<u8> {
+        RlpNodeCodec::<H>::branch_node_nibbled(partial, number_nibble, children, value)
+    }
+}
+
+/// Replace the empty inline child trie-db passes for an omitted child by the empty RLP list, which
+/// [`RlpNodeCodec`] embeds as any inline child.
+fn omitted_as_empty_list<HO>(child: ChildReference<HO>) -> ChildReference<HO>
+where
+    HO: AsMut<[u8]> + Default,
+{
+    match child {
+        ChildReference::Inline(_, 0) => {
+            let mut inline = HO::default();
+            inline.as_mut()[0] = OMITTED_CHILD;
+            ChildReference::Inline(inline, 1)
+        }
+        child => child,
+    }
+}
+
+/// Plan a child reference at `offset` in its parent. Children whose RLP is shorter than 32 bytes
+/// are embedded in the parent as a list rather than referenced by hash, and are planned as the
+/// whole embedded RLP so they can be decoded in place.
+fn child_plan(item: &Rlp, offset: usize, len: usize) -> Result<NodeHandlePlan, DecoderError> {
+    let i = item.payload_info()?;
+    if item.is_list() {
+        Ok(NodeHandlePlan::Inline(checked_range(
+            offset..(offset + i.total()),
+            len,
+        )?))
+    } else {
+        Ok(NodeHandlePlan::Hash(checked_range(
+            (offset + i.header_len)..(offset + i.header_len + i.value_len),
+            len,
+        )?))
+    }
+}
+
+/// Check that a range planned into a node fits within the node's `len` bytes, so that building
+/// the node from its plan cannot slice out of bounds.
+fn checked_range(range: Range<usize>, len: usize) -> Result<Range<usize>, DecoderError> {
+    if range.start <= range.end && range.end <= len {
+        Ok(range)
+    } else {
+        Err(DecoderError::Custom("range out of bounds"))
+    }
+}
+
+/// A decoded trie node that owns its data, for inspecting a node's children and value without
+/// tracking byte ranges. Paths are the node's partial key as nibbles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OwnedNode {
+    Empty,
+    Leaf {
+        path: Vec<u8>,
+        value: Vec<u8>,
+    },
+    /// `child` is the hash of the child node, or its RLP if the child is inlined.
+    Extension {
+        path: Vec<u8>,
+        child: Vec<u8>,
+    },
+    /// Each child is the hash of the child node, or its RLP if the child is inlined.
+    Branch {
+        children: [Option<Vec<u8>>; 16],
+        value: Option<Vec<u8>>,
+    },
+}
+
+/// Decode an RLP trie node into an [`OwnedNode`].
+pub fn decode_node(data: &[u8]) -> Result<OwnedNode, DecoderError> {
+    let node = RlpNodeCodec::<crate::KeccakHasher>::decode(data)?;
+    Ok(match node {
+        Node::Empty => OwnedNode::Empty,
+        Node::Leaf(partial, value) => OwnedNode::Leaf {
+            path: nibble_vec(&partial),
+            value: value_vec(value),
+        },
+        Node::Extension(partial, child) => OwnedNode::Extension {
+            path: nibble_vec(&partial),
+            child: handle_vec(child),
+        },
+        Node::Branch(children, value) => OwnedNode::Branch {
+            children: children.map(|child| child.map(handle_vec)),
+            value: value.map(value_vec),
+        },
+        Node::NibbledBranch(..) => {
+            return Err(DecoderError::Custom("Ethereum branch nodes do not have partial key"))
+        }
+    })
+}
+
+/// The kind of a trie node, as told by [`node_kind`] without decoding the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Empty,
+    Leaf,
+    Extension,
+    Branch,
+}
+
+/// Classify an RLP trie node by its number of items and, for leaves and extensions, the flags
+/// nibble of its partial path. Nothing else is decoded or checked, so a node classified here may
+/// still fail to decode.
+pub fn node_kind(data: &[u8]) -> Result<NodeKind, DecoderError> {
+    let rlp = Rlp::new(data);
+    match rlp.prototype()? {
+        Prototype::Data(0) => Ok(NodeKind::Empty),
+        Prototype::List(17) => Ok(NodeKind::Branch),
+        Prototype::List(2) => {
+            let flags = *rlp.at(0)?.data()?.first().ok_or(DecoderError::RlpIsTooShort)?;
+            if flags & 0x20 == 0x20 {
+                Ok(NodeKind::Leaf)
+            } else {
+                Ok(NodeKind::Extension)
+            }
+        }
+        _ => Err(DecoderError::Custom("Rlp is not valid.")),
+    }
+}
+
+fn nibble_vec(slice: &NibbleSlice) -> Vec<u8> {
+    (0..slice.len()).map(|i| slice.at(i)).collect()
+}
+
+fn value_vec(value: Value) -> Vec<u8> {
+    match value {
+        Value::Inline(bytes) | Value::Node(bytes) => bytes.to_vec(),
+    }
+}
+
+fn handle_vec(handle: NodeHandle) -> Vec<u8> {
+    match handle {
+        NodeHandle::Hash(bytes) | NodeHandle::Inline(bytes) => bytes.to_vec(),
+    }
+}
+
+/// Render a proof as one block per node with its hash, type, nibble path, children and value
+/// length, for logging and bug reports. Nodes that fail to decode are reported in place.
+#[cfg(feature = "std")]
+pub fn dump_proof(proof: &[Vec<u8>]) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::new();
+    for (index, node) in proof.iter().enumerate() {
+        let hash = crate::KeccakHasher::hash(node);
+        let _ = writeln!(out, "#{} 0x{} ({} bytes)", index, hex::encode(hash), node.len());
+        let _ = match decode_node(node) {
+            Err(err) => writeln!(out, "  invalid: {}", err),
+            Ok(OwnedNode::Empty) => writeln!(out, "  empty"),
+            Ok(OwnedNode::Leaf { path, value }) => writeln!(
+                out,
+                "  leaf path={} value={} bytes",
+                dump_nibbles(&path),
+                value.len()
+            ),
+            Ok(OwnedNode::Extension { path, child }) => writeln!(
+                out,
+                "  extension path={} child={}",
+                dump_nibbles(&path),
+                dump_child(&child)
+            ),
+            Ok(OwnedNode::Branch { children, value }) => {
+                let _ = writeln!(out, "  branch");
+                for (nibble, child) in children.iter().enumerate() {
+                    if let Some(child) = child {
+                        let _ = writeln!(out, "    {:x}: {}", nibble, dump_child(child));
+                    }
+                }
+                match value {
+                    Some(value) => writeln!(out, "    value={} bytes", value.len()),
+                    None => writeln!(out, "    value=none"),
+                }
+            }
+        };
+    }
+    out
+}
+
+#[cfg(feature = "std")]
+fn dump_nibbles(nibbles: &[u8]) -> String {
+    nibbles.iter().map(|nibble| format!("{:x}", nibble)).collect()
+}
+
+#[cfg(feature = "std")]
+fn dump_child(child: &[u8]) -> String {
+    if child.len() == 32 {
+        format!("0x{}", hex::encode(child))
+    } else {
+        format!("inline 0x{}", hex::encode(child))
+    }
+}
+
+/// Hex-prefix encode the partial path of a leaf, given as by `NibbleSlice::right_iter`: nibbles
+/// packed two to a byte, with the first nibble of a path of odd length alone in the low half of the
+/// first byte. The parity comes from `number_nibble`, as the number of packed bytes cannot tell a
+/// path of 2 nibbles from one of 1.
+fn compact_encode_leaf(partial: Vec<u8>, number_nibble: usize) -> Vec<u8> {
   let mut encoded = Vec::new();
 
-    if partial.len() % 2 == 1 {
-        encoded.push(0x3 * 16 + partial[0]); // Prefix with 0x3 and take the first nibble
+    if number_nibble % 2 == 1 {
+        encoded.push(0x3 * 16 + first_nibble(&partial)); // Prefix with 0x3
         encoded.extend_from_slice(&partial[1..]);
     } else {
         encoded.push(0x2 * 16); // Prefix with 0x2
@@ -249,11 +543,12 @@ fn compact_encode_leaf(partial: Vec<u8>) -> Vec<u8> {
     encoded
 }
 
-fn compact_encode_extension(partial: Vec<u8>) -> Vec<u8> {
+/// Hex-prefix encode the partial path of an extension, packed as for [`compact_encode_leaf`].
+fn compact_encode_extension(partial: Vec<u8>, number_nibble: usize) -> Vec<u8> {
   let mut encoded = Vec::new();
 
-    if partial.len() % 2 == 1 {
-        encoded.push(0x1 * 16 + partial[0]); // Prefix with 0x1 and take the first nibble
+    if number_nibble % 2 == 1 {
+        encoded.push(0x1 * 16 + first_nibble(&partial)); // Prefix with 0x1
         encoded.extend_from_slice(&partial[1..]);
     } else {
         encoded.push(0x0 * 16); // Prefix with 0x0
@@ -263,40 +558,431 @@ fn compact_encode_extension(partial: Vec<u8>) -> Vec<u8> {
     encoded
 }
 
-fn decode_compact(encoded: &[u8]) -> Vec<u8> {
-  // Decode the compact encoding for the Ethereum MPT.
-  let is_odd = encoded[0] & 0x01 == 0x01;
-  let is_leaf = encoded[0] & 0x02 == 0x02;
+/// The first nibble of a packed partial path of odd length, alone in the low half of its byte.
+///
+/// Panics if the byte holds more than one nibble, which would spill into the hex-prefix flags and
+/// corrupt the node.
+fn first_nibble(partial: &[u8]) -> u8 {
+    let first_nibble = partial[0];
+    assert!(
+        first_nibble < 16,
+        "first byte of odd partial path holds more than one nibble: {first_nibble:#04x}"
+    );
+    first_nibble
+}
 
-  let mut nibbles = vec![];
-  if is_odd {
-      nibbles.push(encoded[0] >> 2);
-  }
+/// The kind of a decoded node, as recorded in `tracing` events.
+#[cfg(feature = "tracing")]
+fn plan_kind(plan: &NodePlan) -> &'static str {
+    match plan {
+        NodePlan::Empty => "empty",
+        NodePlan::Leaf { .. } => "leaf",
+        NodePlan::Extension { .. } => "extension",
+        NodePlan::Branch { .. } => "branch",
+        NodePlan::NibbledBranch { .. } => "nibbled_branch",
+    }
+}
 
-  for byte in &encoded[1..] {
-      nibbles.push(byte >> 4);
-      nibbles.push(byte & 0x0F);
-  }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn it_emits_tracing_event_for_decoded_node() {
+        use std::sync::{Arc, Mutex};
+        use tracing::{
+            field::{Field, Visit},
+            span, Event, Metadata, Subscriber,
+        };
+
+        struct NodeField(Option<String>);
+
+        impl Visit for NodeField {
+            fn record_str(&mut self, field: &Field, value: &str) {
+                if field.name() == "node" {
+                    self.0 = Some(value.to_string());
+                }
+            }
 
-  if is_leaf {
-      nibbles.insert(0, 0x02);
-  } else { 
-      nibbles.insert(0, 0x00);
-  }
+            fn record_debug(&mut self, _: &Field, _: &dyn core::fmt::Debug) {}
+        }
 
-  nibbles
-}
+        /// Records the `node` field of every event.
+        struct NodeEvents(Arc<Mutex<Vec<String>>>);
+
+        impl Subscriber for NodeEvents {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
 
-fn encode_compact(decoded: &[u8], is_leaf: bool) -> Vec<u8> {
-  // Encode the compact encoding for the Ethereum MPT.
-  let mut encoded = vec![];
-  let first_nibble = if is_leaf { 0x02 } else { 0x00 } | if decoded.len() % 2 == 1 { 0x01 } else { 0x00 };
-  encoded.push(first_nibble);
+            fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+                span::Id::from_u64(1)
+            }
 
-  for i in (0..decoded.len()).step_by(2) {
-      let byte = (decoded[i] << 4) | decoded[i + 1];
-      encoded.push(byte);
-  }
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut node = NodeField(None);
+                event.record(&mut node);
+                self.0.lock().unwrap().extend(node.0);
+            }
+
+            fn enter(&self, _: &span::Id) {}
+
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&vec![0x31u8, 0x23]);
+        stream.append(&b"hello".to_vec());
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        tracing::subscriber::with_default(NodeEvents(events.clone()), || {
+            decode_node(&stream.out()).unwrap();
+        });
+
+        assert_eq!(*events.lock().unwrap(), vec!["leaf".to_string()]);
+    }
+
+    #[test]
+    fn it_compact_encodes_packed_partial_paths() {
+        assert_eq!(compact_encode_leaf(vec![0x0a, 0xbc], 3), vec![0x3a, 0xbc]);
+        assert_eq!(compact_encode_leaf(vec![0xab], 2), vec![0x20, 0xab]);
+        assert_eq!(compact_encode_extension(vec![0x0a], 1), vec![0x1a]);
+        assert_eq!(compact_encode_extension(vec![0xab, 0xcd], 4), vec![0x00, 0xab, 0xcd]);
+        assert_eq!(compact_encode_extension(Vec::new(), 0), vec![0x00]);
+    }
+
+    #[test]
+    fn it_encodes_omitted_child_only_for_trie_db_proofs() {
+        type Codec = TrieDbProofCodec<crate::KeccakHasher>;
+        let omitted = || ChildReference::Inline(crate::types::B256::ZERO, 0);
+        let mut children: [Option<ChildReference<crate::types::B256>>; 16] = Default::default();
+        children[3] = Some(omitted());
+        children[7] = Some(ChildReference::Hash(crate::types::B256::repeat_byte(0x11)));
+        let branch = Codec::branch_node(children.iter(), None);
+
+        // Three empty children after the list header, then the omitted one.
+        match Codec::decode_plan(&branch).unwrap() {
+            NodePlan::Branch { children, .. } => {
+                assert_eq!(children[3], Some(NodeHandlePlan::Inline(4..4)));
+                assert!(matches!(children[7], Some(NodeHandlePlan::Hash(_))));
+            }
+            plan => panic!("expected a branch, got {:?}", plan),
+        }
+        assert_eq!(
+            Codec::extension_node([0x0a].into_iter(), 1, omitted()),
+            vec![0xc2, 0x1a, 0xc0]
+        );
+
+        // The Ethereum codec plans the empty list as no child in a branch, and as an inline child,
+        // which is not a valid node, in an extension.
+        match RlpNodeCodec::<crate::KeccakHasher>::decode(&branch).unwrap() {
+            Node::Branch(children, _) => assert_eq!(children[3], None),
+            node => panic!("expected a branch, got {:?}", node),
+        }
+        assert!(decode_node(&[0xc0]).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "holds more than one nibble")]
+    fn it_rejects_odd_partial_path_with_wide_first_nibble() {
+        compact_encode_leaf(vec![0x1a, 0xbc], 3);
+    }
+
+    #[test]
+    fn it_decodes_leaf_node() {
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&vec![0x31u8, 0x23]);
+        stream.append(&b"hello".to_vec());
+
+        assert_eq!(
+            decode_node(&stream.out()),
+            Ok(OwnedNode::Leaf {
+                path: vec![1, 2, 3],
+                value: b"hello".to_vec(),
+            })
+        );
+    }
+
+    #[test]
+    fn it_decodes_extension_node() {
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&vec![0x00u8, 0xab]);
+        stream.append(&vec![0x11u8; 32]);
+
+        assert_eq!(
+            decode_node(&stream.out()),
+            Ok(OwnedNode::Extension {
+                path: vec![0xa, 0xb],
+                child: vec![0x11; 32],
+            })
+        );
+    }
+
+    #[test]
+    fn it_classifies_nodes_without_decoding() {
+        assert_eq!(node_kind(&NULL_NODE), Ok(NodeKind::Empty));
+
+        let mut leaf = RlpStream::new_list(2);
+        leaf.append(&vec![0x31u8, 0x23]);
+        leaf.append(&b"hello".to_vec());
+        assert_eq!(node_kind(&leaf.out()), Ok(NodeKind::Leaf));
+
+        for partial in [vec![0x00u8, 0xab], vec![0x1a]] {
+            let mut extension = RlpStream::new_list(2);
+            extension.append(&partial);
+            extension.append(&vec![0x11u8; 32]);
+            assert_eq!(node_kind(&extension.out()), Ok(NodeKind::Extension));
+        }
+
+        let mut branch = RlpStream::new_list(17);
+        for _ in 0..16 {
+            branch.append_empty_data();
+        }
+        branch.append(&b"value".to_vec());
+        assert_eq!(node_kind(&branch.out()), Ok(NodeKind::Branch));
+
+        assert!(node_kind(&[0xc0]).is_err());
+        assert!(node_kind(&[0xc2, 0x80, 0x80]).is_err());
+        assert!(node_kind(&[]).is_err());
+    }
+
+    #[test]
+    fn it_decodes_branch_node() {
+        let mut stream = RlpStream::new_list(17);
+        for index in 0..16 {
+            if index == 3 {
+                stream.append(&vec![0x22u8; 32]);
+            } else {
+                stream.append_empty_data();
+            }
+        }
+        stream.append_empty_data();
+
+        let mut children: [Option<Vec<u8>>; 16] = Default::default();
+        children[3] = Some(vec![0x22; 32]);
+        assert_eq!(
+            decode_node(&stream.out()),
+            Ok(OwnedNode::Branch {
+                children,
+                value: None,
+            })
+        );
+    }
+
+    #[test]
+    fn it_decodes_leaf_with_long_string_value() {
+        // 56 bytes is the first length with a length-of-length header, 300 needs two length bytes.
+        for len in [55, 56, 300] {
+            let mut stream = RlpStream::new_list(2);
+            stream.append(&vec![0x31u8, 0x23]);
+            stream.append(&vec![0xab; len]);
+            let encoded = stream.out();
+
+            assert_eq!(
+                decode_node(&encoded),
+                Ok(OwnedNode::Leaf {
+                    path: vec![1, 2, 3],
+                    value: vec![0xab; len],
+                })
+            );
+            assert!(
+                RlpNodeCodec::<crate::KeccakHasher>::decode_plan(&encoded[..encoded.len() - 1])
+                    .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn it_decodes_branch_with_long_items() {
+        // An inline child whose list payload is over 55 bytes, ahead of other children.
+        let mut inline = RlpStream::new_list(2);
+        inline.append(&vec![0x20u8]);
+        inline.append(&vec![0x01u8; 56]);
+        let inline = inline.out().to_vec();
+        assert_eq!(inline[..2], [0xf8, 0x3b]);
+
+        let mut stream = RlpStream::new_list(17);
+        for index in 0..16 {
+            match index {
+                0 => stream.append_raw(&inline, 1),
+                5 => stream.append(&vec![0x22u8; 32]),
+                _ => stream.append_empty_data(),
+            };
+        }
+        stream.append(&vec![0xee; 100]);
+
+        let mut children: [Option<Vec<u8>>; 16] = Default::default();
+        children[0] = Some(inline);
+        children[5] = Some(vec![0x22; 32]);
+        assert_eq!(
+            decode_node(&stream.out()),
+            Ok(OwnedNode::Branch {
+                children,
+                value: Some(vec![0xee; 100]),
+            })
+        );
+    }
+
+    #[test]
+    fn it_rejects_empty_partial_key() {
+        // Regression seeds from the `decode_plan` fuzz target.
+        let leaf = [0xc2, 0x80, 0x80];
+        let mut extension = vec![0xe2, 0x80, 0xa0];
+        extension.extend_from_slice(&[0; 32]);
+
+        assert!(RlpNodeCodec::<crate::KeccakHasher>::decode_plan(&leaf).is_err());
+        assert!(RlpNodeCodec::<crate::KeccakHasher>::decode_plan(&extension).is_err());
+    }
+
+    #[test]
+    fn it_follows_extension_with_empty_partial() {
+        use crate::{hex_prefix, verify_proof_hashed_key, EthereumLayout, KeccakHasher};
+
+        let key = [0x12u8; 32];
+        let nibbles: Vec<u8> = key.iter().flat_map(|byte| [byte >> 4, byte & 0xf]).collect();
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&hex_prefix::encode(&nibbles, true));
+        stream.append(&vec![0xab; 40]);
+        let leaf = stream.out().to_vec();
+        let leaf_hash = KeccakHasher::hash(&leaf);
+
+        // The partial is the bare `0x00` flags byte of an even extension.
+        let extension = RlpNodeCodec::<KeccakHasher>::extension_node(
+            core::iter::empty(),
+            0,
+            ChildReference::Hash(leaf_hash),
+        );
+        assert_eq!(extension[..2], [0xe2, 0x00]);
+        assert_eq!(
+            decode_node(&extension),
+            Ok(OwnedNode::Extension {
+                path: Vec::new(),
+                child: leaf_hash.to_vec(),
+            })
+        );
+
+        let root = KeccakHasher::hash(&extension);
+        let proof = vec![extension, leaf];
+        verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &key, Some(&[0xab; 40]))
+            .expect("Failed to verify through extension with empty partial");
+        verify_proof_hashed_key::<EthereumLayout>(&root, &proof, &[0x13; 32], None)
+            .expect("Failed to verify absence through extension with empty partial");
+    }
+
+    #[test]
+    fn it_rejects_truncated_leaf_node() {
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&vec![0x31u8, 0x23]);
+        stream.append(&b"hello".to_vec());
+        let encoded = stream.out();
+
+        for len in 1..encoded.len() {
+            assert!(RlpNodeCodec::<crate::KeccakHasher>::decode_plan(&encoded[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn it_rejects_truncated_branch_node() {
+        let mut stream = RlpStream::new_list(17);
+        for _ in 0..16 {
+            stream.append(&vec![0x22u8; 32]);
+        }
+        stream.append(&b"value".to_vec());
+        let encoded = stream.out();
 
-  encoded
+        for len in 1..encoded.len() {
+            assert!(RlpNodeCodec::<crate::KeccakHasher>::decode_plan(&encoded[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn it_rejects_out_of_bounds_range() {
+        assert_eq!(checked_range(2..5, 5), Ok(2..5));
+        assert_eq!(
+            checked_range(2..6, 5),
+            Err(DecoderError::Custom("range out of bounds"))
+        );
+    }
+
+    #[test]
+    fn it_stores_large_values_inline() {
+        let entries = vec![
+            (b"key-1".to_vec(), vec![0xab; 40]),
+            (b"key-2".to_vec(), vec![0xcd; 40]),
+        ];
+
+        // Root computed with an independent implementation of the trie.
+        let expected: crate::types::B256 =
+            "0x2b8d35aae3ace3cd229491da24c3fea1ae0f244b1eb1d9c521d4e14e724c4aa8"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            crate::compute_state_root::<crate::EthereumLayout>(&entries),
+            expected
+        );
+
+        let (db, root) = crate::tests::test_trie::<crate::EthereumLayout>(&entries);
+        let (proof, _) = crate::generate_proof::<crate::EthereumLayout>(&db, &root, b"key-1").unwrap();
+        match decode_node(proof.last().unwrap()) {
+            Ok(OwnedNode::Leaf { value, .. }) => assert_eq!(value, vec![0xab; 40]),
+            node => panic!("expected a leaf holding the value, got {:?}", node),
+        }
+    }
+
+    #[test]
+    fn it_verifies_proofs_of_long_string_values() {
+        let entries = vec![
+            (b"key-1".to_vec(), vec![0xab; 300]),
+            (b"key-2".to_vec(), vec![0xcd; 100]),
+            (b"key-3".to_vec(), vec![0x01; 56]),
+        ];
+
+        // Root computed with an independent implementation of the trie.
+        let expected: crate::types::B256 =
+            "0xea2204800c53d36ad62dfef15c6c0f9b4100184c1e942e9f0064a0007f3ade8a"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            crate::compute_state_root::<crate::EthereumLayout>(&entries),
+            expected
+        );
+
+        let (db, root) = crate::tests::test_trie::<crate::EthereumLayout>(&entries);
+        assert_eq!(root, expected);
+        for (key, value) in entries.iter() {
+            let (proof, _) =
+                crate::generate_proof::<crate::EthereumLayout>(&db, &root, key).unwrap();
+            crate::verify_proof_raw_key::<crate::EthereumLayout>(&root, &proof, key, Some(value))
+                .expect("Failed to verify proof of long value");
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn it_dumps_proof() {
+        let entries = vec![
+            (b"do".to_vec(), b"verb".to_vec()),
+            (b"dog".to_vec(), b"puppy".to_vec()),
+            (b"horse".to_vec(), b"stallion".to_vec()),
+        ];
+        let (db, root) = crate::tests::test_trie::<crate::EthereumLayout>(&entries);
+        let (proof, _) = crate::generate_proof::<crate::EthereumLayout>(&db, &root, b"dog").unwrap();
+
+        let expected = concat!(
+            "#0 0x64f499057dcfc447a4cd4ed3c48e61be42ad74987018248ba79c95dd59c17533 (115 bytes)\n",
+            "  branch\n",
+            "    4: 0x6401522e6c22d1f0b30a66229bdbbae185e388efe1de3dacc2182f0f7fe550d1\n",
+            "    a: 0xa4a8c217a8f9017ea2a502598b0bc25fc5932412c7926a0703bc7b23231c1525\n",
+            "    c: 0xda74376839326b58475f9644e25ba6a59d16c5120268ec0fa0db58d879800485\n",
+            "    value=none\n",
+            "#1 0x6401522e6c22d1f0b30a66229bdbbae185e388efe1de3dacc2182f0f7fe550d1 (40 bytes)\n",
+            "  leaf path=1791102999c339c844880b23950704cc43aa840f3739e365323cda4dfa89e7a value=5 bytes\n",
+        );
+        assert_eq!(dump_proof(&proof), expected);
+    }
 }
@@ -0,0 +1,97 @@
+//! Serde helpers encoding integers and bytes as `0x`-prefixed lowercase hex strings, following the
+//! Ethereum JSON-RPC conventions.
+
+use crate::rstd::{format, vec::Vec, String};
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+fn strip_prefix<E: Error>(value: &str) -> Result<&str, E> {
+    value
+        .strip_prefix("0x")
+        .ok_or_else(|| E::custom("hex string is missing the 0x prefix"))
+}
+
+fn decode<E: Error>(value: &str) -> Result<Vec<u8>, E> {
+    hex::decode(strip_prefix::<E>(value)?).map_err(E::custom)
+}
+
+/// A `u64` as a quantity, e.g. `0x1`.
+pub(crate) mod quantity {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:#x}", value))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        u64::from_str_radix(strip_prefix(&value)?, 16).map_err(D::Error::custom)
+    }
+}
+
+struct Hex<'a>(&'a [u8]);
+
+impl Serialize for Hex<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("0x{}", hex::encode(self.0)))
+    }
+}
+
+/// Bytes as data, e.g. `0x80`.
+pub(crate) mod bytes {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &[u8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Hex(value).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        decode(&String::deserialize(deserializer)?)
+    }
+}
+
+/// Optional bytes as data, or `null`.
+pub(crate) mod option_bytes {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.as_deref().map(Hex).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|value| decode(&value))
+            .transpose()
+    }
+}
+
+/// A list of byte strings, such as proof nodes, as a list of data.
+pub(crate) mod bytes_list {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &[Vec<u8>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(value.iter().map(|item| Hex(item)))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Vec<u8>>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|value| decode(value))
+            .collect()
+    }
+}
@@ -0,0 +1,141 @@
+//! SSZ framing of proofs, for consensus-layer tooling that expects SSZ lists rather than vectors
+//! of RLP nodes. A proof is serialized as the SSZ type `List[List[byte, N], M]`: a 4-byte
+//! little-endian offset for each node, counted from the start of the list, followed by the nodes
+//! one after another. The nodes themselves stay RLP encoded. The `N` and `M` limits only bear on
+//! the hash tree root, which is not computed here, so any limits the consumer uses will do.
+
+use crate::rstd::vec::Vec;
+
+const OFFSET_LEN: usize = 4;
+
+/// Failure to decode an SSZ list of proof nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// There are fewer bytes than the offsets of the nodes take.
+    InvalidByteLength { len: usize, expected: usize },
+    /// The offset of the first node does not end the offsets, being zero or not a multiple of the
+    /// offset length.
+    InvalidFirstOffset(usize),
+    /// An offset points past the end of the bytes.
+    OffsetOutOfBounds(usize),
+    /// An offset is smaller than the one before it.
+    OffsetsAreDecreasing(usize),
+}
+
+/// Serialize the nodes of a proof, in order, as an SSZ `List[List[byte, N], M]`.
+///
+/// Panics if the serialized proof is longer than `u32::MAX` bytes, the most SSZ offsets can reach.
+pub fn proof_to_ssz(proof: &[Vec<u8>]) -> Vec<u8> {
+    let len = proof.iter().map(|node| OFFSET_LEN + node.len()).sum();
+    let mut bytes = Vec::with_capacity(len);
+    let mut offset = proof.len() * OFFSET_LEN;
+    for node in proof.iter() {
+        let encoded = u32::try_from(offset).expect("SSZ proof longer than u32::MAX bytes");
+        bytes.extend_from_slice(&encoded.to_le_bytes());
+        offset += node.len();
+    }
+    for node in proof.iter() {
+        bytes.extend_from_slice(node);
+    }
+    bytes
+}
+
+/// Deserialize the nodes of a proof from an SSZ `List[List[byte, N], M]`, as serialized by
+/// [`proof_to_ssz`]. The nodes are not decoded.
+pub fn proof_from_ssz(bytes: &[u8]) -> Result<Vec<Vec<u8>>, DecodeError> {
+    if bytes.is_empty() {
+        return Ok(Vec::new());
+    }
+    let first_offset = read_offset(bytes, 0)?;
+    if first_offset == 0 || first_offset % OFFSET_LEN != 0 {
+        return Err(DecodeError::InvalidFirstOffset(first_offset));
+    }
+    if first_offset > bytes.len() {
+        return Err(DecodeError::InvalidByteLength {
+            len: bytes.len(),
+            expected: first_offset,
+        });
+    }
+
+    let count = first_offset / OFFSET_LEN;
+    let mut proof = Vec::with_capacity(count);
+    let mut start = first_offset;
+    for index in 1..=count {
+        let end = if index == count {
+            bytes.len()
+        } else {
+            read_offset(bytes, index * OFFSET_LEN)?
+        };
+        if end > bytes.len() {
+            return Err(DecodeError::OffsetOutOfBounds(end));
+        }
+        if end < start {
+            return Err(DecodeError::OffsetsAreDecreasing(end));
+        }
+        proof.push(bytes[start..end].to_vec());
+        start = end;
+    }
+    Ok(proof)
+}
+
+fn read_offset(bytes: &[u8], at: usize) -> Result<usize, DecodeError> {
+    let encoded = bytes
+        .get(at..at + OFFSET_LEN)
+        .ok_or(DecodeError::InvalidByteLength {
+            len: bytes.len(),
+            expected: at + OFFSET_LEN,
+        })?;
+    let mut offset = [0; OFFSET_LEN];
+    offset.copy_from_slice(encoded);
+    Ok(u32::from_le_bytes(offset) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_trie;
+    use crate::{generate_proof, verify_proof_raw_key, EthereumLayout};
+
+    #[test]
+    fn it_round_trips_proof_through_ssz() {
+        let entries: Vec<_> = (0..8u8).map(|i| (vec![i; 20], vec![i; 40])).collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, &entries[2].0).unwrap();
+
+        let bytes = proof_to_ssz(&proof);
+        let first_offset = (proof.len() * OFFSET_LEN) as u32;
+        assert_eq!(bytes[..OFFSET_LEN], first_offset.to_le_bytes());
+        let decoded = proof_from_ssz(&bytes).unwrap();
+        assert_eq!(decoded, proof);
+        verify_proof_raw_key::<EthereumLayout>(&root, &decoded, &entries[2].0, Some(&entries[2].1))
+            .expect("Failed to verify proof decoded from SSZ");
+
+        assert_eq!(proof_to_ssz(&[]), Vec::<u8>::new());
+        assert_eq!(proof_from_ssz(&[]), Ok(Vec::new()));
+        let with_empty_node = vec![vec![0x80], Vec::new()];
+        assert_eq!(proof_from_ssz(&proof_to_ssz(&with_empty_node)), Ok(with_empty_node));
+    }
+
+    #[test]
+    fn it_rejects_malformed_ssz_offsets() {
+        assert_eq!(
+            proof_from_ssz(&[0x04, 0x00]),
+            Err(DecodeError::InvalidByteLength {
+                len: 2,
+                expected: 4
+            })
+        );
+        assert_eq!(
+            proof_from_ssz(&[0x03, 0x00, 0x00, 0x00]),
+            Err(DecodeError::InvalidFirstOffset(3))
+        );
+        assert_eq!(
+            proof_from_ssz(&[0x08, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00]),
+            Err(DecodeError::OffsetOutOfBounds(9))
+        );
+        assert_eq!(
+            proof_from_ssz(&[0x08, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00]),
+            Err(DecodeError::OffsetsAreDecreasing(6))
+        );
+    }
+}
@@ -0,0 +1,1354 @@
+use crate::rstd::{vec::Vec, BTreeMap};
+use crate::types::{
+    rlp_decode_storage_value, rlp_encode_storage_value, AccountState, Address,
+    EIP1186ProofResponse, B256, U256,
+};
+use crate::eip1186::{index_nodes, process_path_cached, walk_path_strict};
+use crate::utils::keccak256;
+use crate::{
+    generate_proof, leaves_in_proof, verify_proof_raw_key, EthereumLayout, KeccakHasher,
+    VerifyError, EMPTY_TRIE_ROOT,
+};
+
+use hash_db::{HashDBRef, Hasher};
+use primitive_types::{H160, H256};
+use rlp::DecoderError;
+use trie_db::{
+    CError, DBValue, NibbleSlice, NodeCodec, Result as TrieResult, TrieError, TrieHash, TrieLayout,
+};
+
+/// Account proofs keyed by address, as returned in `accountProof` by `eth_getProof`.
+pub type StateProofInput = BTreeMap<Address, Vec<Vec<u8>>>;
+
+/// Storage proofs keyed by address and slot, along with the proven slot value.
+pub type StorageProofInput = BTreeMap<Address, BTreeMap<B256, (U256, Vec<Vec<u8>>)>>;
+
+/// The failure of the account or storage proofs of an account verified by [`verify_proofs`], along
+/// with the address of that account, or `None` if there was no account to verify.
+pub type AccountProofError = (Option<Address>, VerifyError<B256, DecoderError>);
+
+/// Verify the account and storage proofs for every account in `state_tree` and return the state
+/// root they prove against.
+///
+/// Each account is checked against its proof, and each of its storage proofs against the account's
+/// `storage_hash`. Zero slot values are checked as exclusion proofs. Stops at the first account
+/// whose proofs are missing or invalid, or whose account proof does not share the root of the
+/// previous accounts, and returns its address. Fails with [`VerifyError::IncompleteProof`] and no
+/// address if `state_tree` is empty, as there is then no proof of any state root.
+pub fn verify_proofs(
+    state_tree: &BTreeMap<Address, AccountState>,
+    state_proof_input: StateProofInput,
+    storage_proof_input: StorageProofInput,
+) -> Result<B256, AccountProofError> {
+    verify_proofs_detailed(state_tree, state_proof_input, storage_proof_input)
+        .map(|(state_root, _)| state_root)
+}
+
+/// Like [`verify_proofs`], but also return the proven storage root of every account, for chaining
+/// into further storage proof verification.
+pub fn verify_proofs_detailed(
+    state_tree: &BTreeMap<Address, AccountState>,
+    state_proof_input: StateProofInput,
+    storage_proof_input: StorageProofInput,
+) -> Result<(B256, BTreeMap<Address, B256>), AccountProofError> {
+    let mut state_root = None;
+    let mut storage_roots = BTreeMap::new();
+    for (address, account) in state_tree.iter() {
+        let root = state_proof_input
+            .get(address)
+            .and_then(|account_proof| account_proof.first())
+            .map(|root_node| KeccakHasher::hash(root_node))
+            .ok_or((Some(*address), VerifyError::IncompleteProof))?;
+        if state_root.map_or(false, |state_root| state_root != root) {
+            return Err((Some(*address), VerifyError::NoMatchingRoot));
+        }
+
+        let value = account.rlp_encode();
+        verify_proof_raw_key::<EthereumLayout>(
+            &root,
+            &state_proof_input[address],
+            address.as_slice(),
+            Some(&value),
+        )
+        .map_err(|err| (Some(*address), err))?;
+
+        for (slot, (value, proof)) in storage_proof_input.get(address).into_iter().flatten() {
+            let value = (*value != U256::ZERO).then(|| rlp_encode_storage_value(value));
+            verify_proof_raw_key::<EthereumLayout>(
+                &account.storage_hash,
+                proof,
+                slot.as_slice(),
+                value.as_deref(),
+            )
+            .map_err(|err| (Some(*address), err))?;
+        }
+
+        storage_roots.insert(*address, account.storage_hash);
+        state_root = Some(root);
+    }
+
+    let state_root = state_root.ok_or((None, VerifyError::IncompleteProof))?;
+    Ok((state_root, storage_roots))
+}
+
+/// A storage slot of a [`VerifiableAccount`], with its value and the proof of that value against
+/// the account's `storage_hash`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiableSlot {
+    pub slot: B256,
+    /// Zero for slots that are not set, which are proven by exclusion.
+    pub value: U256,
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// An account along with the proof of its state against the state root, and the proofs of some of
+/// its storage slots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiableAccount {
+    pub address: Address,
+    pub account: AccountState,
+    pub account_proof: Vec<Vec<u8>>,
+    pub slots: Vec<VerifiableSlot>,
+}
+
+/// Split `eth_getProof` responses, each of which combines an account and its storage proofs, into
+/// one [`VerifiableAccount`] per response, in order.
+pub fn split_proofs(responses: Vec<EIP1186ProofResponse>) -> Vec<VerifiableAccount> {
+    responses
+        .into_iter()
+        .map(|response| VerifiableAccount {
+            address: response.address,
+            account: AccountState {
+                nonce: response.nonce,
+                balance: response.balance,
+                storage_hash: response.storage_hash,
+                code_hash: response.code_hash,
+            },
+            account_proof: response.account_proof,
+            slots: response
+                .storage_proof
+                .into_iter()
+                .map(|slot| VerifiableSlot {
+                    slot: slot.key,
+                    value: slot.value,
+                    proof: slot.proof,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// A storage slot along with its value and the proof of that value.
+pub struct StorageSlotProof {
+    pub slot: H256,
+    /// Zero for slots that are not set, which are proven by exclusion.
+    pub value: U256,
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// The proof of an account and of a set of its storage slots, with the storage proofs rooted at
+/// the `storage_hash` of the proven account.
+pub struct AccountAndStorageProof {
+    pub address: H160,
+    /// `None` when the account does not exist, in which case the storage proofs are against the
+    /// empty trie.
+    pub account: Option<AccountState>,
+    pub account_proof: Vec<Vec<u8>>,
+    pub storage_proofs: Vec<StorageSlotProof>,
+}
+
+/// Generate the proof of an account in the state trie at `state_root` along with the proofs of
+/// `slots` in its storage trie, as for an access-list style witness.
+pub fn generate_account_and_storage_proof<L>(
+    state_db: &dyn HashDBRef<L::Hash, DBValue>,
+    storage_db: &dyn HashDBRef<L::Hash, DBValue>,
+    state_root: &TrieHash<L>,
+    address: &H160,
+    slots: &[H256],
+) -> TrieResult<AccountAndStorageProof, TrieHash<L>, CError<L>>
+where
+    L: TrieLayout,
+    CError<L>: From<DecoderError>,
+{
+    let (account_proof, item) = generate_proof::<L>(state_db, state_root, address.as_bytes())?;
+    let account = item
+        .map(|item| AccountState::rlp_decode(&item))
+        .transpose()
+        .map_err(|err| TrieError::DecoderError(*state_root, err.into()))?;
+
+    let storage_root = storage_root::<L>(account.as_ref());
+    let storage_proofs = slots
+        .iter()
+        .map(|slot| {
+            let (proof, item) = generate_proof::<L>(storage_db, &storage_root, slot.as_bytes())?;
+            let value = item
+                .map(|item| rlp_decode_storage_value(&item))
+                .transpose()
+                .map_err(|err| TrieError::DecoderError(storage_root, err.into()))?
+                .unwrap_or(U256::ZERO);
+            Ok(StorageSlotProof {
+                slot: *slot,
+                value,
+                proof,
+            })
+        })
+        .collect::<TrieResult<Vec<_>, TrieHash<L>, CError<L>>>()?;
+
+    Ok(AccountAndStorageProof {
+        address: *address,
+        account,
+        account_proof,
+        storage_proofs,
+    })
+}
+
+/// Verify an [`AccountAndStorageProof`]: the account against `state_root`, and every slot against
+/// the storage root of the proven account.
+pub fn verify_account_and_storage_proof<L>(
+    state_root: &TrieHash<L>,
+    proof: &AccountAndStorageProof,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    let value = proof.account.as_ref().map(AccountState::rlp_encode);
+    verify_proof_raw_key::<L>(
+        state_root,
+        &proof.account_proof,
+        proof.address.as_bytes(),
+        value.as_deref(),
+    )?;
+
+    let storage_root = storage_root::<L>(proof.account.as_ref());
+    for slot in proof.storage_proofs.iter() {
+        let value = (slot.value != U256::ZERO).then(|| rlp_encode_storage_value(&slot.value));
+        verify_proof_raw_key::<L>(
+            &storage_root,
+            &slot.proof,
+            slot.slot.as_bytes(),
+            value.as_deref(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Verify the proof of `account` at `address` in the state trie at `state_root`, then verify every
+/// storage proof against exactly the `storage_hash` of that account, so that storage values are
+/// bound to the state root through the account. A `None` or zero value is checked as an exclusion
+/// proof. Fails with [`VerifyError::StorageRootMismatch`] if the root node of a storage proof does
+/// not hash to the account's `storage_hash`.
+pub fn verify_linked_proof<L>(
+    state_root: &TrieHash<L>,
+    address: &H160,
+    account_proof: &[Vec<u8>],
+    account: &AccountState,
+    storage_proofs: &[(H256, Option<U256>, Vec<Vec<u8>>)],
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    let value = account.rlp_encode();
+    verify_proof_raw_key::<L>(state_root, account_proof, address.as_bytes(), Some(&value))?;
+
+    let storage_root = trie_hash::<L>(&account.storage_hash);
+    for (slot, value, proof) in storage_proofs.iter() {
+        if let Some(root_node) = proof.first() {
+            let found = <L::Hash as Hasher>::hash(root_node);
+            if found != storage_root {
+                return Err(VerifyError::StorageRootMismatch {
+                    expected: storage_root,
+                    found,
+                });
+            }
+        }
+        let value = value
+            .filter(|value| *value != U256::ZERO)
+            .map(|value| rlp_encode_storage_value(&value));
+        verify_proof_raw_key::<L>(&storage_root, proof, slot.as_bytes(), value.as_deref())?;
+    }
+    Ok(())
+}
+
+/// Verify the proof of the account at `address` in the state trie at `state_root`, supplied in
+/// path order, with `None` for an account that does not exist. Account keys are all 32 bytes, so
+/// an account can only be stored in a leaf; a proof that ends at a value in a branch is malformed
+/// and fails with [`VerifyError::ValueInBranchForFixedKey`].
+pub fn verify_account_proof<L>(
+    state_root: &TrieHash<L>,
+    proof: &[Vec<u8>],
+    address: &H160,
+    expected: Option<&AccountState>,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    let key = <L::Hash as Hasher>::hash(address.as_bytes());
+    let end = walk_path_strict::<L, _>(state_root, proof, NibbleSlice::new(key.as_ref()), None)?;
+    if end.in_branch && end.value.is_some() {
+        return Err(VerifyError::ValueInBranchForFixedKey);
+    }
+    let value = expected.map(AccountState::rlp_encode);
+    end.check(value.as_deref())
+}
+
+/// Verify the proof of the account at `address` in the state trie at `state_root`, supplied in
+/// path order, and check only its `code_hash` against `expected_code_hash`, whatever its balance,
+/// nonce and storage. Fails with [`VerifyError::ValueMismatch`] carrying the proven account if the
+/// code hash differs, and with [`VerifyError::NonExistingValue`] if the account does not exist.
+pub fn verify_code_hash_proof<L>(
+    state_root: &TrieHash<L>,
+    address: &H160,
+    account_proof: &[Vec<u8>],
+    expected_code_hash: B256,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+    CError<L>: From<DecoderError>,
+{
+    let key = <L::Hash as Hasher>::hash(address.as_bytes());
+    let key = NibbleSlice::new(key.as_ref());
+    let end = walk_path_strict::<L, _>(state_root, account_proof, key, None)?;
+    let value = end.found()?;
+    let account = AccountState::rlp_decode(value).map_err(|err| VerifyError::DecodeError {
+        depth: end.depth,
+        source: err.into(),
+    })?;
+    if account.code_hash != expected_code_hash {
+        return Err(VerifyError::ValueMismatch(value.to_vec()));
+    }
+    Ok(())
+}
+
+/// Verify the proof of the account at `address` in the state trie at `state_root`, supplied in
+/// path order, and check only its `nonce` against `expected_nonce`. Fails with
+/// [`VerifyError::NonceMismatch`] if the nonce differs, and with [`VerifyError::NonExistingValue`]
+/// if the account does not exist.
+pub fn verify_nonce_proof<L>(
+    state_root: &TrieHash<L>,
+    address: &H160,
+    account_proof: &[Vec<u8>],
+    expected_nonce: u64,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+    CError<L>: From<DecoderError>,
+{
+    let key = <L::Hash as Hasher>::hash(address.as_bytes());
+    let key = NibbleSlice::new(key.as_ref());
+    let end = walk_path_strict::<L, _>(state_root, account_proof, key, None)?;
+    let account = AccountState::rlp_decode(end.found()?).map_err(|err| VerifyError::DecodeError {
+        depth: end.depth,
+        source: err.into(),
+    })?;
+    if account.nonce != expected_nonce {
+        return Err(VerifyError::NonceMismatch {
+            expected: expected_nonce,
+            found: account.nonce,
+        });
+    }
+    Ok(())
+}
+
+/// Whether the storage trie of `account` is empty, as for an account without code, a contract that
+/// never wrote to storage or one that self-destructed in the block that created it.
+pub fn verify_empty_storage(account: &AccountState) -> bool {
+    account.storage_hash == EMPTY_TRIE_ROOT
+}
+
+/// Check that `computed_storage_root`, such as the root of a storage trie rebuilt from a full dump
+/// of a contract's slots, is the `storage_hash` of `account`. Fails with
+/// [`VerifyError::StorageRootMismatch`] otherwise.
+pub fn verify_storage_root_against_account(
+    account: &AccountState,
+    computed_storage_root: B256,
+) -> Result<(), VerifyError<B256, DecoderError>> {
+    if account.storage_hash != computed_storage_root {
+        return Err(VerifyError::StorageRootMismatch {
+            expected: account.storage_hash,
+            found: computed_storage_root,
+        });
+    }
+    Ok(())
+}
+
+/// Verify the proof of the account at `address` in the state trie at `state_root`, supplied in
+/// path order, and check that its storage is empty. Fails with
+/// [`VerifyError::StorageRootMismatch`] if the proven account has storage, and with
+/// [`VerifyError::NonExistingValue`] if the account does not exist.
+pub fn verify_account_has_empty_storage<L>(
+    state_root: &TrieHash<L>,
+    address: &H160,
+    account_proof: &[Vec<u8>],
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+    CError<L>: From<DecoderError>,
+{
+    let key = <L::Hash as Hasher>::hash(address.as_bytes());
+    let key = NibbleSlice::new(key.as_ref());
+    let end = walk_path_strict::<L, _>(state_root, account_proof, key, None)?;
+    let account = AccountState::rlp_decode(end.found()?).map_err(|err| VerifyError::DecodeError {
+        depth: end.depth,
+        source: err.into(),
+    })?;
+    if !verify_empty_storage(&account) {
+        return Err(VerifyError::StorageRootMismatch {
+            expected: trie_hash::<L>(&EMPTY_TRIE_ROOT),
+            found: trie_hash::<L>(&account.storage_hash),
+        });
+    }
+    Ok(())
+}
+
+/// Verify that every account proof roots to the `stateRoot` of a block header, tying the proven
+/// accounts to that block. Stops at the first proof that fails and returns it along with the
+/// address of its account.
+pub fn verify_against_header<L>(
+    header_state_root: &B256,
+    account_proofs: &[(H160, AccountState, Vec<Vec<u8>>)],
+) -> Result<(), (H160, VerifyError<TrieHash<L>, CError<L>>)>
+where
+    L: TrieLayout,
+{
+    let state_root = trie_hash::<L>(header_state_root);
+    for (address, account, proof) in account_proofs.iter() {
+        let value = account.rlp_encode();
+        verify_proof_raw_key::<L>(&state_root, proof, address.as_bytes(), Some(&value))
+            .map_err(|err| (*address, err))?;
+    }
+    Ok(())
+}
+
+/// A key of a Solidity mapping, as it is hashed with the mapping's slot to locate its entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappingKey<'a> {
+    /// A value-type key encoded as a 32-byte word, as by `abi.encode`: left-padded with zeros for
+    /// `address`, `bool` and unsigned integers, sign-extended for signed integers and right-padded
+    /// with zeros for `bytesN`. The caller pads the key, since the word alone does not say which.
+    Word(B256),
+    /// A `string` or `bytes` key, hashed unpadded whatever its length.
+    Bytes(&'a [u8]),
+}
+
+impl MappingKey<'_> {
+    /// The storage slot of the entry for this key in the mapping declared at `base_slot`:
+    /// `keccak256(key ++ base_slot)`, with the key encoded as described for each variant.
+    pub fn storage_slot(&self, base_slot: U256) -> B256 {
+        let key = match self {
+            MappingKey::Word(word) => word.as_slice(),
+            MappingKey::Bytes(bytes) => *bytes,
+        };
+        let mut preimage = Vec::with_capacity(key.len() + 32);
+        preimage.extend_from_slice(key);
+        preimage.extend_from_slice(&base_slot.to_be_bytes::<32>());
+        keccak256(&preimage)
+    }
+}
+
+/// Verify the proof of the entry for `map_key` in a Solidity mapping declared at `base_slot`, such
+/// as an ERC20 `balanceOf`, in the storage trie at `storage_root`. The entry is stored at
+/// [`MappingKey::storage_slot`] and proven under the keccak of that slot. An `expected` value of
+/// `None` or zero is checked as an exclusion proof.
+pub fn verify_mapping_slot_proof<L>(
+    storage_root: &TrieHash<L>,
+    proof: &[Vec<u8>],
+    base_slot: U256,
+    map_key: MappingKey<'_>,
+    expected: Option<&U256>,
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    let slot = map_key.storage_slot(base_slot);
+    let value = expected
+        .filter(|value| **value != U256::ZERO)
+        .map(rlp_encode_storage_value);
+    verify_proof_raw_key::<L>(storage_root, proof, slot.as_slice(), value.as_deref())
+}
+
+/// Verify the proof of `slot` in the storage trie at `storage_root`, supplied in path order, and
+/// return the proven value. A slot that is not in the trie is zero, as in the EVM. Fails with
+/// [`VerifyError::DecodeError`] at the depth of the last proof node if the proven value is not the
+/// RLP of an integer of at most 32 bytes without leading zeros.
+pub fn verify_storage_proof_value<L>(
+    storage_root: &TrieHash<L>,
+    proof: &[Vec<u8>],
+    slot: &B256,
+) -> Result<U256, VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+    CError<L>: From<DecoderError>,
+{
+    let key = <L::Hash as Hasher>::hash(slot.as_slice());
+    let end = walk_path_strict::<L, _>(storage_root, proof, NibbleSlice::new(key.as_ref()), None)?;
+    match end.value {
+        None => Ok(U256::ZERO),
+        Some(value) => rlp_decode_storage_value(value).map_err(|err| VerifyError::DecodeError {
+            depth: end.depth,
+            source: err.into(),
+        }),
+    }
+}
+
+/// The value of `slot` before and after a change, given its proof under the storage root of each
+/// state. Each proof is verified against its own root, and a slot that is not in a trie is `None`.
+pub fn diff_storage_proofs<L>(
+    before: (B256, Vec<Vec<u8>>),
+    after: (B256, Vec<Vec<u8>>),
+    slot: H256,
+) -> Result<(Option<U256>, Option<U256>), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+    CError<L>: From<DecoderError>,
+{
+    let slot = B256::from(slot.0);
+    let value = |(root, proof): (B256, Vec<Vec<u8>>)| {
+        verify_storage_proof_value::<L>(&trie_hash::<L>(&root), &proof, &slot)
+            .map(|value| (value != U256::ZERO).then_some(value))
+    };
+    Ok((value(before)?, value(after)?))
+}
+
+/// Verify the proofs of many accounts in the state trie at `state_root` at once, with `None` for
+/// accounts that do not exist. The proofs are merged into a single set of nodes indexed by hash, so
+/// the upper nodes they share are hashed and decoded once instead of once per account. Stops at
+/// the first account that fails to verify.
+pub fn verify_accounts_batch<L>(
+    state_root: &TrieHash<L>,
+    accounts: &[(H160, Option<AccountState>, Vec<Vec<u8>>)],
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    let mut unique = BTreeMap::new();
+    for node in accounts.iter().flat_map(|(_, _, proof)| proof.iter()) {
+        unique.insert(node.as_slice(), ());
+    }
+    let nodes: BTreeMap<_, _> = unique
+        .into_keys()
+        .map(|node| (<L::Hash as Hasher>::hash(node), node))
+        .collect();
+
+    let mut plans = BTreeMap::new();
+    for (address, account, _) in accounts.iter() {
+        let key = <L::Hash as Hasher>::hash(address.as_bytes());
+        let value = account.as_ref().map(AccountState::rlp_encode);
+        process_path_cached::<L>(
+            state_root,
+            &nodes,
+            &mut plans,
+            NibbleSlice::new(key.as_ref()),
+            value.as_deref(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Verify many slots of one contract against a storage proof or multiproof covering them all, such
+/// as the nodes of the `storageProof` entries `eth_getProof` returns for several slots, in any
+/// order. Slots expected to be `None` or zero are checked as exclusion proofs. The nodes are
+/// indexed by hash once, and the upper nodes the slots share are decoded once. Stops at the first
+/// slot that fails to verify.
+pub fn verify_storage_slots<L>(
+    storage_root: &TrieHash<L>,
+    slots: &[(H256, Option<U256>)],
+    proof: &[Vec<u8>],
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    let nodes = index_nodes::<L>(proof);
+    let mut plans = BTreeMap::new();
+    for (slot, expected) in slots.iter() {
+        let key = <L::Hash as Hasher>::hash(slot.as_bytes());
+        let value = expected
+            .filter(|value| *value != U256::ZERO)
+            .map(|value| rlp_encode_storage_value(&value));
+        process_path_cached::<L>(
+            storage_root,
+            &nodes,
+            &mut plans,
+            NibbleSlice::new(key.as_ref()),
+            value.as_deref(),
+        )?;
+    }
+    Ok(())
+}
+
+/// The trie keys of the slots a storage proof or multiproof covers: the key, as hashed by
+/// [`crate::utils::storage_trie_key`], of every leaf reachable from `storage_root` through the
+/// proof, in key order. Nodes may be supplied in any order. Leaves whose path is not 32 bytes long,
+/// which a storage trie cannot hold, are skipped.
+pub fn covered_slots<L>(
+    storage_root: &TrieHash<L>,
+    proof: &[Vec<u8>],
+) -> Result<Vec<B256>, VerifyError<TrieHash<L>, CError<L>>>
+where
+    L: TrieLayout,
+{
+    let leaves = leaves_in_proof::<L>(storage_root, proof)?;
+    Ok(leaves
+        .into_iter()
+        .filter(|(path, _)| path.len() == 64)
+        .map(|(path, _)| {
+            let mut key = B256::ZERO;
+            for (byte, nibbles) in key.iter_mut().zip(path.chunks(2)) {
+                *byte = nibbles[0] << 4 | nibbles[1];
+            }
+            key
+        })
+        .collect())
+}
+
+/// The root of an account's storage trie, which is the empty trie for accounts that do not exist.
+fn storage_root<L: TrieLayout>(account: Option<&AccountState>) -> TrieHash<L> {
+    match account {
+        Some(account) => trie_hash::<L>(&account.storage_hash),
+        None => <L::Codec as NodeCodec>::hashed_null_node(),
+    }
+}
+
+fn trie_hash<L: TrieLayout>(hash: &B256) -> TrieHash<L> {
+    let mut trie_hash = TrieHash::<L>::default();
+    trie_hash.as_mut().copy_from_slice(hash.as_slice());
+    trie_hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_trie;
+    use crate::utils::to_b256;
+
+    use serde_json::Value;
+    use trie_db::{TrieDBMutBuilder, TrieMut};
+
+    fn bytes(value: &Value) -> Vec<u8> {
+        hex::decode(value.as_str().unwrap().trim_start_matches("0x")).unwrap()
+    }
+
+    fn proof(value: &Value) -> Vec<Vec<u8>> {
+        value.as_array().unwrap().iter().map(bytes).collect()
+    }
+
+    fn fixture() -> (
+        BTreeMap<Address, AccountState>,
+        StateProofInput,
+        StorageProofInput,
+    ) {
+        let json: Value =
+            serde_json::from_str(include_str!("../tests/data/proof_data.json")).unwrap();
+        let address: Address = json["address"].as_str().unwrap().parse().unwrap();
+        let account = AccountState {
+            nonce: u64::from_str_radix(
+                json["nonce"].as_str().unwrap().trim_start_matches("0x"),
+                16,
+            )
+            .unwrap(),
+            balance: json["balance"].as_str().unwrap().parse().unwrap(),
+            storage_hash: json["storageHash"].as_str().unwrap().parse().unwrap(),
+            code_hash: json["codeHash"].as_str().unwrap().parse().unwrap(),
+        };
+
+        let storage = json["storageProof"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|slot| {
+                let key: B256 = slot["key"].as_str().unwrap().parse().unwrap();
+                let value: U256 = slot["value"].as_str().unwrap().parse().unwrap();
+                (key, (value, proof(&slot["proof"])))
+            })
+            .collect();
+
+        (
+            BTreeMap::from([(address, account)]),
+            BTreeMap::from([(address, proof(&json["accountProof"]))]),
+            BTreeMap::from([(address, storage)]),
+        )
+    }
+
+    #[test]
+    fn it_verifies_fixture_proofs() {
+        let (state_tree, state_proof_input, storage_proof_input) = fixture();
+
+        let state_root =
+            verify_proofs(&state_tree, state_proof_input, storage_proof_input).unwrap();
+
+        let expected: B256 = "0x16a116384acc08d85d4e0304a4323304205d1f1eb49457bd15d83362d81a2d44"
+            .parse()
+            .unwrap();
+        assert_eq!(state_root, expected);
+    }
+
+    #[test]
+    fn it_verifies_fixture_proofs_with_account_built_from_trace() {
+        let (_, state_proof_input, storage_proof_input) = fixture();
+        let trace: Value =
+            serde_json::from_str(include_str!("../tests/data/trace_data.json")).unwrap();
+        let state_tree = trace
+            .as_object()
+            .unwrap()
+            .iter()
+            .map(|(address, state)| {
+                let storage = state["storage"]
+                    .as_object()
+                    .unwrap()
+                    .iter()
+                    .map(|(slot, value)| {
+                        (slot.parse().unwrap(), value.as_str().unwrap().parse().unwrap())
+                    })
+                    .collect();
+                let account = AccountState::from_contents(
+                    state["nonce"].as_u64().unwrap(),
+                    state["balance"].as_str().unwrap().parse().unwrap(),
+                    &bytes(&state["code"]),
+                    &storage,
+                );
+                (address.parse().unwrap(), account)
+            })
+            .collect();
+
+        let state_root =
+            verify_proofs(&state_tree, state_proof_input, storage_proof_input).unwrap();
+
+        let expected: B256 = "0x16a116384acc08d85d4e0304a4323304205d1f1eb49457bd15d83362d81a2d44"
+            .parse()
+            .unwrap();
+        assert_eq!(state_root, expected);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_splits_fixture_proof_response_into_verifiable_units() {
+        let response: EIP1186ProofResponse =
+            serde_json::from_str(include_str!("../tests/data/proof_data.json")).unwrap();
+
+        let accounts = split_proofs(vec![response]);
+
+        assert_eq!(accounts.len(), 1);
+        let account = &accounts[0];
+        assert_eq!(account.slots.len(), 2);
+        assert_eq!(account.slots[1].value, U256::from(1));
+
+        let (state_tree, state_proof_input, storage_proof_input) = fixture();
+        assert_eq!(state_tree[&account.address], account.account);
+        assert_eq!(state_proof_input[&account.address], account.account_proof);
+        for slot in account.slots.iter() {
+            let (value, proof) = &storage_proof_input[&account.address][&slot.slot];
+            assert_eq!((&slot.value, &slot.proof), (value, proof));
+        }
+    }
+
+    #[test]
+    fn it_reports_account_with_invalid_proof() {
+        let (mut state_tree, mut state_proof_input, storage_proof_input) = fixture();
+        let (address, account) = state_tree.iter().next().unwrap();
+        let (address, mut account) = (*address, account.clone());
+        account.nonce += 1;
+        state_tree.insert(address, account);
+
+        let (failed, err) =
+            verify_proofs(&state_tree, state_proof_input.clone(), storage_proof_input.clone())
+                .unwrap_err();
+        assert_eq!(failed, Some(address));
+        assert!(matches!(err, VerifyError::ValueMismatch(_)));
+
+        let (state_tree, _, _) = fixture();
+        let account_proof = state_proof_input.get_mut(&address).unwrap();
+        let last = account_proof.len() - 1;
+        account_proof[last][4] ^= 1;
+        assert_eq!(
+            verify_proofs(&state_tree, state_proof_input, storage_proof_input)
+                .map_err(|(address, _)| address),
+            Err(Some(address))
+        );
+    }
+
+    #[test]
+    fn it_rejects_empty_state_tree() {
+        assert_eq!(
+            verify_proofs(&BTreeMap::new(), BTreeMap::new(), BTreeMap::new()),
+            Err((None, VerifyError::IncompleteProof))
+        );
+    }
+
+    #[test]
+    fn it_rejects_storage_proof_not_linked_to_account() {
+        let (state_tree, state_proof_input, storage_proof_input) = fixture();
+        let (address, account) = state_tree.iter().next().unwrap();
+        let account_proof = &state_proof_input[address];
+        let address = H160::from_slice(address.as_slice());
+        let state_root = KeccakHasher::hash(&account_proof[0]);
+        let mut storage_proofs: Vec<_> = storage_proof_input
+            .values()
+            .flatten()
+            .map(|(slot, (value, proof))| (H256::from(slot.0), Some(*value), proof.clone()))
+            .collect();
+
+        verify_linked_proof::<EthereumLayout>(
+            &state_root,
+            &address,
+            account_proof,
+            account,
+            &storage_proofs,
+        )
+        .expect("Failed to verify linked account and storage proofs");
+
+        let slot = H256::repeat_byte(0x01);
+        let (db, other_root) = test_trie::<EthereumLayout>(&[(
+            slot.as_bytes().to_vec(),
+            rlp_encode_storage_value(&U256::from(7)),
+        )]);
+        let (proof, _) =
+            generate_proof::<EthereumLayout>(&db, &other_root, slot.as_bytes()).unwrap();
+        storage_proofs.push((slot, Some(U256::from(7)), proof));
+
+        assert_eq!(
+            verify_linked_proof::<EthereumLayout>(
+                &state_root,
+                &address,
+                account_proof,
+                account,
+                &storage_proofs,
+            ),
+            Err(VerifyError::StorageRootMismatch {
+                expected: account.storage_hash,
+                found: other_root,
+            })
+        );
+    }
+
+    #[test]
+    fn it_verifies_code_hash_of_proven_account() {
+        let (state_tree, state_proof_input, _) = fixture();
+        let (address, account) = state_tree.iter().next().unwrap();
+        let proof = &state_proof_input[address];
+        let state_root = KeccakHasher::hash(&proof[0]);
+        let address = H160::from_slice(address.as_slice());
+
+        verify_code_hash_proof::<EthereumLayout>(&state_root, &address, proof, account.code_hash)
+            .expect("Failed to verify code hash");
+        assert_eq!(
+            verify_code_hash_proof::<EthereumLayout>(
+                &state_root,
+                &address,
+                proof,
+                B256::repeat_byte(0x11)
+            ),
+            Err(VerifyError::ValueMismatch(account.rlp_encode()))
+        );
+        assert!(verify_code_hash_proof::<EthereumLayout>(
+            &state_root,
+            &H160::repeat_byte(0x11),
+            proof,
+            account.code_hash
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn it_verifies_nonce_of_proven_account() {
+        let (state_tree, state_proof_input, _) = fixture();
+        let (address, account) = state_tree.iter().next().unwrap();
+        let proof = &state_proof_input[address];
+        let state_root = KeccakHasher::hash(&proof[0]);
+        let address = H160::from_slice(address.as_slice());
+
+        verify_nonce_proof::<EthereumLayout>(&state_root, &address, proof, account.nonce)
+            .expect("Failed to verify nonce");
+        assert_eq!(
+            verify_nonce_proof::<EthereumLayout>(&state_root, &address, proof, account.nonce + 1),
+            Err(VerifyError::NonceMismatch {
+                expected: account.nonce + 1,
+                found: account.nonce,
+            })
+        );
+    }
+
+    #[test]
+    fn it_verifies_storage_root_matching_account() {
+        let slots: Vec<_> = (1..=4u64)
+            .map(|slot| {
+                let value = rlp_encode_storage_value(&U256::from(slot * 7));
+                (H256::from_low_u64_be(slot).as_bytes().to_vec(), value)
+            })
+            .collect();
+        let storage_root = crate::compute_state_root::<EthereumLayout>(&slots);
+        let account = AccountState {
+            nonce: 1,
+            balance: U256::from(10),
+            storage_hash: storage_root,
+            code_hash: B256::repeat_byte(0x22),
+        };
+        assert_eq!(verify_storage_root_against_account(&account, storage_root), Ok(()));
+    }
+
+    #[test]
+    fn it_rejects_storage_root_not_matching_account() {
+        let account = AccountState {
+            nonce: 1,
+            balance: U256::from(10),
+            storage_hash: B256::repeat_byte(0x33),
+            code_hash: B256::repeat_byte(0x22),
+        };
+        assert_eq!(
+            verify_storage_root_against_account(&account, EMPTY_TRIE_ROOT),
+            Err(VerifyError::StorageRootMismatch {
+                expected: B256::repeat_byte(0x33),
+                found: EMPTY_TRIE_ROOT,
+            })
+        );
+    }
+
+    #[test]
+    fn it_verifies_account_with_empty_storage() {
+        let empty = AccountState {
+            nonce: 1,
+            balance: U256::from(7),
+            storage_hash: EMPTY_TRIE_ROOT,
+            code_hash: B256::repeat_byte(0x11),
+        };
+        let with_storage = AccountState {
+            storage_hash: B256::repeat_byte(0x22),
+            ..empty.clone()
+        };
+        assert!(verify_empty_storage(&empty));
+        assert!(!verify_empty_storage(&with_storage));
+
+        let entries = vec![
+            (H160::repeat_byte(1).as_bytes().to_vec(), empty.rlp_encode()),
+            (H160::repeat_byte(2).as_bytes().to_vec(), with_storage.rlp_encode()),
+        ];
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let proof_of = |address: &H160| {
+            generate_proof::<EthereumLayout>(&db, &root, address.as_bytes()).unwrap().0
+        };
+
+        let address = H160::repeat_byte(1);
+        verify_account_has_empty_storage::<EthereumLayout>(&root, &address, &proof_of(&address))
+            .expect("Failed to verify account with empty storage");
+        let address = H160::repeat_byte(2);
+        assert_eq!(
+            verify_account_has_empty_storage::<EthereumLayout>(
+                &root,
+                &address,
+                &proof_of(&address)
+            ),
+            Err(VerifyError::StorageRootMismatch {
+                expected: EMPTY_TRIE_ROOT,
+                found: B256::repeat_byte(0x22),
+            })
+        );
+    }
+
+    #[test]
+    fn it_rejects_account_value_in_branch() {
+        let (state_tree, state_proof_input, _) = fixture();
+        let (address, account) = state_tree.iter().next().unwrap();
+        let proof = &state_proof_input[address];
+        let state_root = KeccakHasher::hash(&proof[0]);
+        let address = H160::from_slice(address.as_slice());
+        verify_account_proof::<EthereumLayout>(&state_root, proof, &address, Some(account))
+            .expect("Failed to verify well-formed account proof");
+
+        // A trie keyed by raw paths, with a longer key below the account key so the account is
+        // stored in the value slot of a branch.
+        let key = KeccakHasher::hash(address.as_bytes());
+        let mut longer_key = key.to_vec();
+        longer_key.push(0x00);
+        let mut db = crate::empty_db();
+        let mut root = Default::default();
+        {
+            let mut trie = TrieDBMutBuilder::<EthereumLayout>::new(&mut db, &mut root).build();
+            trie.insert(key.as_slice(), &account.rlp_encode()).unwrap();
+            trie.insert(&longer_key, &account.rlp_encode()).unwrap();
+        }
+        let (proof, item) =
+            generate_proof::<EthereumLayout>(&db, &root, address.as_bytes()).unwrap();
+        assert_eq!(item, Some(account.rlp_encode()));
+        verify_proof_raw_key::<EthereumLayout>(
+            &root,
+            &proof,
+            address.as_bytes(),
+            Some(&account.rlp_encode()),
+        )
+        .unwrap();
+        assert_eq!(
+            verify_account_proof::<EthereumLayout>(&root, &proof, &address, Some(account)),
+            Err(VerifyError::ValueInBranchForFixedKey)
+        );
+    }
+
+    #[test]
+    fn it_verifies_account_proofs_against_header_state_root() {
+        let accounts: Vec<_> = (1..=2u8)
+            .map(|byte| {
+                let account = AccountState {
+                    nonce: byte as u64,
+                    balance: U256::from(byte),
+                    storage_hash: crate::EMPTY_TRIE_ROOT,
+                    code_hash: B256::repeat_byte(byte),
+                };
+                (H160::repeat_byte(byte), account)
+            })
+            .collect();
+        let entries: Vec<_> = accounts
+            .iter()
+            .map(|(address, account)| (address.as_bytes().to_vec(), account.rlp_encode()))
+            .collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let mut account_proofs: Vec<_> = accounts
+            .into_iter()
+            .map(|(address, account)| {
+                let (proof, _) =
+                    generate_proof::<EthereumLayout>(&db, &root, address.as_bytes()).unwrap();
+                (address, account, proof)
+            })
+            .collect();
+        let header_state_root = root;
+
+        assert_eq!(
+            verify_against_header::<EthereumLayout>(&header_state_root, &account_proofs),
+            Ok(())
+        );
+
+        account_proofs[1].1.nonce += 1;
+        let (address, _) =
+            verify_against_header::<EthereumLayout>(&header_state_root, &account_proofs)
+                .unwrap_err();
+        assert_eq!(address, H160::repeat_byte(2));
+    }
+
+    #[test]
+    fn it_returns_proven_storage_roots() {
+        let (state_tree, state_proof_input, storage_proof_input) = fixture();
+
+        let (_, storage_roots) =
+            verify_proofs_detailed(&state_tree, state_proof_input, storage_proof_input).unwrap();
+
+        let json: Value =
+            serde_json::from_str(include_str!("../tests/data/proof_data.json")).unwrap();
+        let address: Address = json["address"].as_str().unwrap().parse().unwrap();
+        let storage_hash: B256 = json["storageHash"].as_str().unwrap().parse().unwrap();
+        assert_eq!(storage_roots, BTreeMap::from([(address, storage_hash)]));
+    }
+
+    #[test]
+    fn it_verifies_erc20_balance_mapping_slot_proof() {
+        let holder = H160::repeat_byte(0x11);
+        let other = H160::repeat_byte(0x22);
+        let balance = U256::from(1_000_000_000_000_000_000u64);
+        // `mapping(address => uint256) balanceOf` declared at slot 3.
+        let base_slot = U256::from(3);
+        let holder_key = MappingKey::Word(to_b256(H256::from(holder)));
+        let slot = crate::utils::mapping_storage_slot(&holder, H256::from_low_u64_be(3));
+        assert_eq!(holder_key.storage_slot(base_slot), slot);
+        let entries = vec![(slot.to_vec(), rlp_encode_storage_value(&balance))];
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, slot.as_slice()).unwrap();
+        verify_mapping_slot_proof::<EthereumLayout>(
+            &root,
+            &proof,
+            base_slot,
+            holder_key,
+            Some(&balance),
+        )
+        .expect("Failed to verify balanceOf slot proof");
+        assert!(verify_mapping_slot_proof::<EthereumLayout>(
+            &root,
+            &proof,
+            U256::from(4),
+            holder_key,
+            Some(&balance),
+        )
+        .is_err());
+
+        let slot = crate::utils::mapping_storage_slot(&other, H256::from_low_u64_be(3));
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, slot.as_slice()).unwrap();
+        verify_mapping_slot_proof::<EthereumLayout>(
+            &root,
+            &proof,
+            base_slot,
+            MappingKey::Word(to_b256(H256::from(other))),
+            Some(&U256::ZERO),
+        )
+        .expect("Failed to verify unset balanceOf slot proof");
+    }
+
+    #[test]
+    fn it_verifies_mapping_slot_proof_with_dynamic_key() {
+        // `mapping(string => uint256)` declared at slot 5, keyed by a string longer than a word.
+        let key = MappingKey::Bytes(b"a string key that is longer than thirty-two bytes");
+        let base_slot = U256::from(5);
+        let slot: B256 = "0x6c5a42567e9556a3e100762d14d6e7f64220350046188722617a9a929ca7b43c"
+            .parse()
+            .unwrap();
+        assert_eq!(key.storage_slot(base_slot), slot);
+
+        let value = U256::from(42);
+        let entries = vec![(slot.to_vec(), rlp_encode_storage_value(&value))];
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, slot.as_slice()).unwrap();
+        verify_mapping_slot_proof::<EthereumLayout>(&root, &proof, base_slot, key, Some(&value))
+            .expect("Failed to verify string-keyed slot proof");
+    }
+
+    #[test]
+    fn it_verifies_accounts_in_batch() {
+        let mut accounts: Vec<_> = (1..=50u8)
+            .map(|byte| {
+                let account = AccountState {
+                    nonce: byte as u64,
+                    balance: U256::from(byte),
+                    storage_hash: crate::EMPTY_TRIE_ROOT,
+                    code_hash: B256::repeat_byte(byte),
+                };
+                (H160::repeat_byte(byte), Some(account))
+            })
+            .collect();
+        let entries: Vec<_> = accounts
+            .iter()
+            .map(|(address, account)| {
+                (address.as_bytes().to_vec(), account.as_ref().unwrap().rlp_encode())
+            })
+            .collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        accounts.push((H160::repeat_byte(0xff), None));
+        let mut accounts: Vec<_> = accounts
+            .into_iter()
+            .map(|(address, account)| {
+                let (proof, _) =
+                    generate_proof::<EthereumLayout>(&db, &root, address.as_bytes()).unwrap();
+                (address, account, proof)
+            })
+            .collect();
+
+        for (address, account, proof) in accounts.iter() {
+            let value = account.as_ref().map(AccountState::rlp_encode);
+            verify_proof_raw_key::<EthereumLayout>(
+                &root,
+                proof,
+                address.as_bytes(),
+                value.as_deref(),
+            )
+            .expect("Failed to verify account proof");
+        }
+        assert_eq!(verify_accounts_batch::<EthereumLayout>(&root, &accounts), Ok(()));
+
+        accounts[20].1.as_mut().unwrap().nonce += 1;
+        let (address, account, proof) = &accounts[20];
+        let value = account.as_ref().map(AccountState::rlp_encode);
+        let expected = verify_proof_raw_key::<EthereumLayout>(
+            &root,
+            proof,
+            address.as_bytes(),
+            value.as_deref(),
+        );
+        assert!(expected.is_err());
+        assert_eq!(verify_accounts_batch::<EthereumLayout>(&root, &accounts), expected);
+    }
+
+    #[test]
+    fn it_verifies_storage_slots_against_shared_multiproof() {
+        let slots: Vec<_> = (1..=10u64).map(H256::from_low_u64_be).collect();
+        let entries: Vec<_> = slots
+            .iter()
+            .enumerate()
+            .map(|(index, slot)| {
+                let value = U256::from(index as u64 * 1000 + 1);
+                (slot.as_bytes().to_vec(), rlp_encode_storage_value(&value))
+            })
+            .collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+
+        let mut multiproof = Vec::new();
+        let mut per_slot_nodes = 0;
+        for slot in slots.iter() {
+            let (proof, _) = generate_proof::<EthereumLayout>(&db, &root, slot.as_bytes()).unwrap();
+            per_slot_nodes += proof.len();
+            for node in proof {
+                if !multiproof.contains(&node) {
+                    multiproof.push(node);
+                }
+            }
+        }
+        assert!(multiproof.len() < per_slot_nodes);
+
+        let mut expected: Vec<_> = slots
+            .iter()
+            .enumerate()
+            .map(|(index, slot)| (*slot, Some(U256::from(index as u64 * 1000 + 1))))
+            .collect();
+        let absent = H256::from_low_u64_be(0xff);
+        let (absent_proof, _) =
+            generate_proof::<EthereumLayout>(&db, &root, absent.as_bytes()).unwrap();
+        multiproof.extend(absent_proof);
+        expected.push((absent, None));
+        expected.push((absent, Some(U256::ZERO)));
+        assert_eq!(verify_storage_slots::<EthereumLayout>(&root, &expected, &multiproof), Ok(()));
+        multiproof.reverse();
+        assert_eq!(verify_storage_slots::<EthereumLayout>(&root, &expected, &multiproof), Ok(()));
+
+        expected[4].1 = Some(U256::from(5));
+        assert!(verify_storage_slots::<EthereumLayout>(&root, &expected, &multiproof).is_err());
+        expected[4].1 = None;
+        assert!(matches!(
+            verify_storage_slots::<EthereumLayout>(&root, &expected, &multiproof),
+            Err(VerifyError::ExistingValue(_))
+        ));
+    }
+
+    #[test]
+    fn it_diffs_storage_proofs_of_slot_set_from_zero() {
+        let slot = H256::from_low_u64_be(7);
+        let other = (
+            H256::from_low_u64_be(1).as_bytes().to_vec(),
+            rlp_encode_storage_value(&U256::from(1)),
+        );
+        let (before_db, before_root) = test_trie::<EthereumLayout>(&[other.clone()]);
+        let value = U256::from(0xbeef);
+        let entries = vec![other, (slot.as_bytes().to_vec(), rlp_encode_storage_value(&value))];
+        let (after_db, after_root) = test_trie::<EthereumLayout>(&entries);
+
+        let (before_proof, _) =
+            generate_proof::<EthereumLayout>(&before_db, &before_root, slot.as_bytes()).unwrap();
+        let (after_proof, _) =
+            generate_proof::<EthereumLayout>(&after_db, &after_root, slot.as_bytes()).unwrap();
+
+        assert_eq!(
+            diff_storage_proofs::<EthereumLayout>(
+                (before_root, before_proof.clone()),
+                (after_root, after_proof.clone()),
+                slot
+            ),
+            Ok((None, Some(value)))
+        );
+        assert!(diff_storage_proofs::<EthereumLayout>(
+            (after_root, before_proof),
+            (after_root, after_proof),
+            slot
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn it_lists_slots_covered_by_storage_multiproof() {
+        let entries: Vec<_> = (1..=5u8)
+            .map(|byte| {
+                let slot = B256::with_last_byte(byte);
+                (slot.to_vec(), rlp_encode_storage_value(&U256::from(byte)))
+            })
+            .collect();
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let slots = [1u8, 3, 4].map(B256::with_last_byte);
+        let mut proof = Vec::new();
+        for slot in slots.iter() {
+            let (slot_proof, _) =
+                generate_proof::<EthereumLayout>(&db, &root, slot.as_slice()).unwrap();
+            proof.extend(slot_proof);
+        }
+
+        let mut expected = slots.map(crate::utils::storage_trie_key).to_vec();
+        expected.sort();
+        assert_eq!(covered_slots::<EthereumLayout>(&root, &proof), Ok(expected));
+    }
+
+    #[test]
+    fn it_returns_verified_storage_value() {
+        let slots = [B256::with_last_byte(1), B256::with_last_byte(2)];
+        let value = U256::from(0x1234);
+        let entries = vec![
+            (slots[0].to_vec(), rlp_encode_storage_value(&value)),
+            (slots[1].to_vec(), vec![0x82, 0x00, 0x01]),
+            (B256::with_last_byte(3).to_vec(), vec![0xc1, 0x01]),
+            (B256::with_last_byte(4).to_vec(), rlp::encode(&vec![0xff; 33]).to_vec()),
+        ];
+        let (db, root) = test_trie::<EthereumLayout>(&entries);
+        let proof = |slot: &B256| {
+            generate_proof::<EthereumLayout>(&db, &root, slot.as_slice())
+                .unwrap()
+                .0
+        };
+
+        assert_eq!(
+            verify_storage_proof_value::<EthereumLayout>(&root, &proof(&slots[0]), &slots[0]),
+            Ok(value)
+        );
+        let absent = B256::with_last_byte(5);
+        assert_eq!(
+            verify_storage_proof_value::<EthereumLayout>(&root, &proof(&absent), &absent),
+            Ok(U256::ZERO)
+        );
+        assert!(
+            verify_storage_proof_value::<EthereumLayout>(&root, &proof(&slots[0]), &slots[1])
+                .is_err()
+        );
+
+        for (byte, err) in [
+            (2, DecoderError::RlpInvalidIndirection),
+            (3, DecoderError::RlpExpectedToBeData),
+            (4, DecoderError::RlpIsTooBig),
+        ] {
+            let slot = B256::with_last_byte(byte);
+            let slot_proof = proof(&slot);
+            assert_eq!(
+                verify_storage_proof_value::<EthereumLayout>(&root, &slot_proof, &slot),
+                Err(VerifyError::DecodeError {
+                    depth: slot_proof.len() - 1,
+                    source: err
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn it_generates_and_verifies_account_and_storage_proof() {
+        let slots = [
+            H256::from_low_u64_be(0),
+            H256::from_low_u64_be(1),
+            H256::from_low_u64_be(2),
+        ];
+        let values = [U256::from(0x1234), U256::MAX];
+        let storage_entries: Vec<_> = slots
+            .iter()
+            .zip(values.iter())
+            .map(|(slot, value)| (slot.as_bytes().to_vec(), rlp_encode_storage_value(value)))
+            .collect();
+        let (storage_db, storage_root) = test_trie::<EthereumLayout>(&storage_entries);
+
+        let address = H160::repeat_byte(0x11);
+        let account = AccountState {
+            nonce: 7,
+            balance: U256::from(1_000_000),
+            storage_hash: storage_root,
+            code_hash: B256::repeat_byte(0x22),
+        };
+        let state_entries = vec![
+            (address.as_bytes().to_vec(), account.rlp_encode()),
+            (
+                H160::repeat_byte(0x33).as_bytes().to_vec(),
+                account.rlp_encode(),
+            ),
+        ];
+        let (state_db, state_root) = test_trie::<EthereumLayout>(&state_entries);
+
+        let mut proof = generate_account_and_storage_proof::<EthereumLayout>(
+            &state_db,
+            &storage_db,
+            &state_root,
+            &address,
+            &slots,
+        )
+        .unwrap();
+        assert_eq!(proof.account.as_ref().map(|account| account.nonce), Some(7));
+        let proven: Vec<_> = proof.storage_proofs.iter().map(|slot| slot.value).collect();
+        assert_eq!(proven, vec![values[0], values[1], U256::ZERO]);
+        verify_account_and_storage_proof::<EthereumLayout>(&state_root, &proof)
+            .expect("Failed to verify account and storage proof");
+
+        proof.storage_proofs[0].value = U256::from(0x4321);
+        assert!(verify_account_and_storage_proof::<EthereumLayout>(&state_root, &proof).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+//! Stateless execution with revm against the state proven by account and storage proofs. The proof
+//! nodes are rebuilt into a partial trie database with [`build_partial_db`], and every account and
+//! storage slot revm reads is looked up from the state root through it. The database is keyed by
+//! node hash, so each lookup is authenticated against the root, and one that needs a node missing
+//! from the proofs fails instead of reading unproven state.
+
+use crate::rstd::{vec::Vec, BTreeMap, Box};
+use crate::types::{rlp_decode_storage_value, AccountState, Address, B256, U256};
+use crate::{build_partial_db, EthereumLayout, EthereumMemoryDB, KeccakHasher};
+
+use hash_db::Hasher;
+use revm::primitives::{AccountInfo, Bytecode, KECCAK_EMPTY};
+use revm::Database;
+use rlp::DecoderError;
+use trie_db::{Trie, TrieDBBuilder, TrieError};
+
+/// Failure of a [`StatelessDb`] lookup.
+#[derive(Debug)]
+pub enum StatelessDbError {
+    /// The lookup needs a node the proofs do not include, or one that does not decode.
+    Trie(Box<TrieError<B256, DecoderError>>),
+    /// An account or storage value proven in the trie is not validly encoded.
+    Decode(DecoderError),
+    /// The code of an account was not supplied with [`StatelessDb::insert_code`].
+    MissingCode(B256),
+    /// The hash of a block was not supplied with [`StatelessDb::insert_block_hash`].
+    MissingBlockHash(U256),
+}
+
+impl From<Box<TrieError<B256, DecoderError>>> for StatelessDbError {
+    fn from(err: Box<TrieError<B256, DecoderError>>) -> Self {
+        Self::Trie(err)
+    }
+}
+
+/// A revm [`Database`] serving the accounts and storage proven under a state root. Code and block
+/// hashes are not part of the state trie and are supplied separately.
+pub struct StatelessDb {
+    db: EthereumMemoryDB,
+    state_root: B256,
+    code: BTreeMap<B256, Bytecode>,
+    block_hashes: BTreeMap<U256, B256>,
+}
+
+impl StatelessDb {
+    /// Rebuild the state under `state_root` from `nodes`, the nodes of account proofs and of the
+    /// storage proofs of those accounts, in any order.
+    pub fn new(state_root: B256, nodes: &[Vec<u8>]) -> Self {
+        Self {
+            db: build_partial_db(nodes),
+            state_root,
+            code: BTreeMap::new(),
+            block_hashes: BTreeMap::new(),
+        }
+    }
+
+    /// Supply the code of a proven account, keyed by its hash.
+    pub fn insert_code(&mut self, code: Bytecode) {
+        self.code.insert(code.hash_slow(), code);
+    }
+
+    /// Supply the hash of block `number`, for the `BLOCKHASH` opcode.
+    pub fn insert_block_hash(&mut self, number: U256, hash: B256) {
+        self.block_hashes.insert(number, hash);
+    }
+
+    /// The proven state of the account at `address`, or `None` if it does not exist.
+    pub fn account(&self, address: &Address) -> Result<Option<AccountState>, StatelessDbError> {
+        self.lookup(&self.state_root, address.as_slice())?
+            .map(|value| AccountState::rlp_decode(&value).map_err(StatelessDbError::Decode))
+            .transpose()
+    }
+
+    /// Look up the value under the hash of `key` in the trie at `root`.
+    fn lookup(&self, root: &B256, key: &[u8]) -> Result<Option<Vec<u8>>, StatelessDbError> {
+        let trie = TrieDBBuilder::<EthereumLayout>::new(&self.db, root).build();
+        Ok(trie.get(KeccakHasher::hash(key).as_ref())?)
+    }
+}
+
+impl Database for StatelessDb {
+    type Error = StatelessDbError;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        Ok(self.account(&address)?.map(|account| AccountInfo {
+            balance: account.balance,
+            nonce: account.nonce,
+            code_hash: account.code_hash,
+            code: self.code.get(&account.code_hash).cloned(),
+        }))
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        if code_hash == KECCAK_EMPTY {
+            return Ok(Bytecode::new());
+        }
+        self.code.get(&code_hash).cloned().ok_or(StatelessDbError::MissingCode(code_hash))
+    }
+
+    /// Slots of accounts that do not exist, and slots not in the storage trie, are zero.
+    fn storage(&mut self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        let storage_root = match self.account(&address)? {
+            Some(account) => account.storage_hash,
+            None => return Ok(U256::ZERO),
+        };
+        match self.lookup(&storage_root, &index.to_be_bytes::<32>())? {
+            Some(value) => rlp_decode_storage_value(&value).map_err(StatelessDbError::Decode),
+            None => Ok(U256::ZERO),
+        }
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
+        self.block_hashes
+            .get(&number)
+            .copied()
+            .ok_or(StatelessDbError::MissingBlockHash(number))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_proof;
+    use crate::tests::test_trie;
+    use crate::types::rlp_encode_storage_value;
+
+    use revm::primitives::{ExecutionResult, TransactTo};
+    use revm::EVM;
+
+    fn account(balance: u64, storage_hash: B256) -> AccountState {
+        AccountState {
+            nonce: 0,
+            balance: U256::from(balance),
+            storage_hash,
+            code_hash: KECCAK_EMPTY,
+        }
+    }
+
+    #[test]
+    fn it_runs_transfer_against_proven_state() {
+        let slot = U256::from(1);
+        let slot_key = slot.to_be_bytes::<32>();
+        let storage_entries = vec![(slot_key.to_vec(), rlp_encode_storage_value(&U256::from(42)))];
+        let (storage_db, storage_root) = test_trie::<EthereumLayout>(&storage_entries);
+
+        let sender = Address::repeat_byte(0x01);
+        let receiver = Address::repeat_byte(0x02);
+        let fillers: Vec<_> = (0x10..0x30u8).map(Address::repeat_byte).collect();
+        let mut entries = vec![
+            (sender.to_vec(), account(1_000_000, crate::EMPTY_TRIE_ROOT).rlp_encode()),
+            (receiver.to_vec(), account(5, storage_root).rlp_encode()),
+        ];
+        entries.extend(fillers.iter().map(|address| {
+            (address.to_vec(), account(1, crate::EMPTY_TRIE_ROOT).rlp_encode())
+        }));
+        let (state_db, state_root) = test_trie::<EthereumLayout>(&entries);
+
+        // The transaction also reads the block's beneficiary, which is proven absent.
+        let mut nodes = Vec::new();
+        for address in [sender, receiver, Address::ZERO] {
+            let (proof, _) =
+                generate_proof::<EthereumLayout>(&state_db, &state_root, address.as_slice())
+                    .unwrap();
+            nodes.extend(proof);
+        }
+        let (proof, _) =
+            generate_proof::<EthereumLayout>(&storage_db, &storage_root, &slot_key).unwrap();
+        nodes.extend(proof);
+        let mut db = StatelessDb::new(state_root, &nodes);
+
+        assert_eq!(db.storage(receiver, slot).unwrap(), U256::from(42));
+        assert!(matches!(db.storage(receiver, U256::from(2)), Ok(value) if value == U256::ZERO));
+        assert!(fillers
+            .iter()
+            .any(|address| matches!(db.basic(*address), Err(StatelessDbError::Trie(_)))));
+
+        let mut evm = EVM::new();
+        evm.database(db);
+        evm.env.tx.caller = sender;
+        evm.env.tx.transact_to = TransactTo::Call(receiver);
+        evm.env.tx.value = U256::from(1_000);
+        evm.env.tx.gas_limit = 21_000;
+        evm.env.tx.gas_price = U256::ZERO;
+        let outcome = evm.transact().unwrap();
+
+        assert!(matches!(outcome.result, ExecutionResult::Success { .. }));
+        assert_eq!(outcome.state[&sender].info.balance, U256::from(999_000));
+        assert_eq!(outcome.state[&receiver].info.balance, U256::from(1_005));
+    }
+}
@@ -0,0 +1,604 @@
+use crate::node_codec::RlpNodeCodec;
+use crate::rstd::{vec::Vec, BTreeMap};
+use crate::utils::keccak256;
+use crate::{compute_state_root, EthereumLayout, KeccakHasher, VerifyError};
+
+pub use alloy_primitives::{Address, B256, U256};
+use primitive_types::H256;
+use core::ops::Deref;
+use rlp::{DecoderError, Rlp, RlpStream};
+use trie_db::NodeCodec;
+
+/// Conversion into a [`B256`] from the other 32-byte hash representations in use, which cannot
+/// implement `Into<B256>` for `H256` as both types are foreign.
+pub trait IntoB256 {
+    fn into_b256(self) -> B256;
+}
+
+impl IntoB256 for B256 {
+    fn into_b256(self) -> B256 {
+        self
+    }
+}
+
+impl IntoB256 for [u8; 32] {
+    fn into_b256(self) -> B256 {
+        B256::from(self)
+    }
+}
+
+impl IntoB256 for H256 {
+    fn into_b256(self) -> B256 {
+        B256::from(self.0)
+    }
+}
+
+impl<T: IntoB256 + Copy> IntoB256 for &T {
+    fn into_b256(self) -> B256 {
+        (*self).into_b256()
+    }
+}
+
+/// The state of an account as stored in the leaves of the state trie. With the `serde` feature it
+/// uses the field names and hex encoding of `eth_getProof`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct AccountState {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::quantity"))]
+    pub nonce: u64,
+    pub balance: U256,
+    /// Root of the account's storage trie.
+    pub storage_hash: B256,
+    /// Keccak of the account's code.
+    pub code_hash: B256,
+}
+
+impl AccountState {
+    /// Build the state of an account from its full contents, as found in execution traces: the
+    /// code hash is computed from `code`, and the storage root from the non-zero `storage` slots.
+    pub fn from_contents(
+        nonce: u64,
+        balance: U256,
+        code: &[u8],
+        storage: &BTreeMap<B256, U256>,
+    ) -> Self {
+        let entries: Vec<_> = storage
+            .iter()
+            .filter(|(_, value)| **value != U256::ZERO)
+            .map(|(slot, value)| (slot.to_vec(), rlp_encode_storage_value(value)))
+            .collect();
+        Self {
+            nonce,
+            balance,
+            storage_hash: compute_state_root::<EthereumLayout>(&entries),
+            code_hash: keccak256(code),
+        }
+    }
+
+    /// RLP encode the account as `[nonce, balance, storageHash, codeHash]`, the value stored in
+    /// the state trie.
+    pub fn rlp_encode(&self) -> Vec<u8> {
+        encode_account_leaf(self.nonce, self.balance, self.storage_hash, self.code_hash)
+    }
+
+    /// Decode an account from the RLP stored in the state trie.
+    pub fn rlp_decode(bytes: &[u8]) -> Result<Self, DecoderError> {
+        let rlp = Rlp::new(bytes);
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(Self {
+            nonce: rlp.val_at(0)?,
+            balance: decode_u256(&rlp.at(1)?)?,
+            storage_hash: decode_hash(&rlp.at(2)?)?,
+            code_hash: decode_hash(&rlp.at(3)?)?,
+        })
+    }
+}
+
+/// RLP encode an account from its fields as `[nonce, balance, storageRoot, codeHash]`, the value
+/// of its leaf in the state trie, as compared against account proofs.
+pub fn encode_account_leaf(
+    nonce: u64,
+    balance: U256,
+    storage_root: B256,
+    code_hash: B256,
+) -> Vec<u8> {
+    let mut stream = RlpStream::new_list(4);
+    stream.append(&nonce);
+    stream.append(&balance.to_be_bytes_trimmed_vec());
+    stream.append(&storage_root.as_slice());
+    stream.append(&code_hash.as_slice());
+    stream.out().to_vec()
+}
+
+/// A proof of `key` in the trie at `root`, along with the proven value, or `None` for an
+/// exclusion proof. `key` is the unhashed key, as for [`crate::verify_proof_raw_key`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProofBundle {
+    pub root: B256,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::bytes"))]
+    pub key: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::bytes_list"))]
+    pub proof: Vec<Vec<u8>>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::option_bytes"))]
+    pub value: Option<Vec<u8>>,
+}
+
+/// The response to `eth_getProof`: the proof of an account and of some of its storage slots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct EIP1186ProofResponse {
+    pub address: Address,
+    pub balance: U256,
+    pub code_hash: B256,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::quantity"))]
+    pub nonce: u64,
+    pub storage_hash: B256,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::bytes_list"))]
+    pub account_proof: Vec<Vec<u8>>,
+    pub storage_proof: Vec<StorageProof>,
+}
+
+/// The proof of a storage slot in an [`EIP1186ProofResponse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StorageProof {
+    pub key: B256,
+    pub value: U256,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::bytes_list"))]
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// A non-empty list of proof nodes that have all been checked to decode as trie nodes. It derefs
+/// to the node slice, so it can be passed to any of the verification functions in place of a
+/// `Vec<Vec<u8>>` proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Proof(Vec<Vec<u8>>);
+
+impl Proof {
+    /// Check that `nodes` is not empty, failing with [`VerifyError::IncompleteProof`] otherwise,
+    /// and that every node decodes, failing with [`VerifyError::DecodeError`] at the index of the
+    /// first one that does not.
+    pub fn try_new(nodes: Vec<Vec<u8>>) -> Result<Self, VerifyError<B256, DecoderError>> {
+        if nodes.is_empty() {
+            return Err(VerifyError::IncompleteProof);
+        }
+        for (depth, node) in nodes.iter().enumerate() {
+            RlpNodeCodec::<KeccakHasher>::decode_plan(node)
+                .map_err(|source| VerifyError::DecodeError { depth, source })?;
+        }
+        Ok(Self(nodes))
+    }
+
+    /// Keccak of the first node, which is the root a proof in path order verifies against.
+    pub fn root_hash(&self) -> B256 {
+        keccak256(&self.0[0])
+    }
+
+    pub fn into_nodes(self) -> Vec<Vec<u8>> {
+        self.0
+    }
+}
+
+impl Deref for Proof {
+    type Target = [Vec<u8>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Encode the nodes of a proof, in order, as a single RLP list of byte strings, the form some APIs
+/// transport proofs in instead of a JSON array.
+pub fn encode_rlp_proof(proof: &[Vec<u8>]) -> Vec<u8> {
+    let mut stream = RlpStream::new_list(proof.len());
+    for node in proof.iter() {
+        stream.append(node);
+    }
+    stream.out().to_vec()
+}
+
+/// Decode a proof from a single RLP list of byte strings, as encoded by [`encode_rlp_proof`]. The
+/// nodes are not decoded. Fails if `blob` is not exactly one list, or if an item of the list is not
+/// a byte string.
+pub fn decode_rlp_proof(blob: &[u8]) -> Result<Vec<Vec<u8>>, DecoderError> {
+    let rlp = Rlp::new(blob);
+    if !rlp.is_list() {
+        return Err(DecoderError::RlpExpectedToBeList);
+    }
+    if rlp.payload_info()?.total() != blob.len() {
+        return Err(DecoderError::RlpInconsistentLengthAndData);
+    }
+    rlp.iter()
+        .map(|node| {
+            if !node.is_data() {
+                return Err(DecoderError::RlpExpectedToBeData);
+            }
+            node.data().map(<[u8]>::to_vec)
+        })
+        .collect()
+}
+
+pub(crate) fn decode_hash(rlp: &Rlp) -> Result<B256, DecoderError> {
+    let data = rlp.data()?;
+    if data.len() != 32 {
+        return Err(DecoderError::RlpInvalidLength);
+    }
+    Ok(B256::from_slice(data))
+}
+
+/// RLP encode a storage value the way it is stored in the storage trie: as a string of the value's
+/// big-endian bytes with leading zeros trimmed, as geth does. Zero encodes to the empty string
+/// `0x80`, although zero slots are not stored in the trie and are proven by exclusion instead.
+pub fn rlp_encode_storage_value(value: &U256) -> Vec<u8> {
+    rlp::encode(&value.to_be_bytes_trimmed_vec()).to_vec()
+}
+
+/// RLP encode a storage value as a string of all 32 of its big-endian bytes, leading zeros
+/// included, for display alongside tools that show slots in their fixed width. This is NOT how
+/// values are stored in the storage trie, and proofs must be checked against
+/// [`rlp_encode_storage_value`] instead.
+pub fn rlp_encode_storage_value_fixed(value: &U256) -> Vec<u8> {
+    rlp::encode(&value.to_be_bytes::<32>().to_vec()).to_vec()
+}
+
+/// Decode a storage value encoded as by [`rlp_encode_storage_value`], rejecting lists, trailing
+/// bytes and leading zeros.
+pub fn rlp_decode_storage_value(bytes: &[u8]) -> Result<U256, DecoderError> {
+    let rlp = Rlp::new(bytes);
+    if rlp.payload_info()?.total() != bytes.len() {
+        return Err(DecoderError::RlpInconsistentLengthAndData);
+    }
+    decode_u256(&rlp)
+}
+
+/// Decode a big-endian integer of at most 32 bytes without leading zeros, as `rlp` decodes other
+/// integers, rather than truncating longer ones.
+fn decode_u256(rlp: &Rlp) -> Result<U256, DecoderError> {
+    if !rlp.is_data() {
+        return Err(DecoderError::RlpExpectedToBeData);
+    }
+    let data = rlp.data()?;
+    if data.len() > 32 {
+        return Err(DecoderError::RlpIsTooBig);
+    }
+    if data.first() == Some(&0) {
+        return Err(DecoderError::RlpInvalidIndirection);
+    }
+    Ok(U256::from_be_slice(data))
+}
+
+#[cfg(feature = "revm")]
+impl From<&revm::primitives::AccountInfo> for AccountState {
+    /// `AccountInfo` does not carry the storage root, so it defaults to the empty trie. Use
+    /// [`AccountState::from_account_info`] for accounts with storage.
+    fn from(info: &revm::primitives::AccountInfo) -> Self {
+        Self::from_account_info(info, crate::EMPTY_TRIE_ROOT)
+    }
+}
+
+#[cfg(feature = "revm")]
+impl AccountState {
+    /// Build the state of an account executed with revm, given the root of its storage trie.
+    pub fn from_account_info(info: &revm::primitives::AccountInfo, storage_hash: B256) -> Self {
+        Self {
+            nonce: info.nonce,
+            balance: info.balance,
+            storage_hash,
+            code_hash: info.code_hash,
+        }
+    }
+}
+
+#[cfg(feature = "revm")]
+impl TryFrom<&AccountState> for revm::primitives::AccountInfo {
+    type Error = core::convert::Infallible;
+
+    /// The storage root is dropped and the code is left unloaded, as `AccountInfo` only refers to
+    /// it by `code_hash`.
+    fn try_from(account: &AccountState) -> Result<Self, Self::Error> {
+        Ok(Self {
+            balance: account.balance,
+            nonce: account.nonce,
+            code_hash: account.code_hash,
+            code: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_compares_account_states() {
+        let account = AccountState {
+            nonce: 1,
+            balance: U256::from(2),
+            storage_hash: crate::EMPTY_TRIE_ROOT,
+            code_hash: B256::repeat_byte(0x22),
+        };
+
+        assert_eq!(account.clone(), account);
+        assert_ne!(
+            AccountState {
+                nonce: 2,
+                ..account.clone()
+            },
+            account
+        );
+    }
+
+    #[test]
+    fn it_encodes_account_leaf_matching_account_proof() {
+        use crate::node_codec::{decode_node, OwnedNode};
+        use serde_json::Value;
+
+        let json: Value =
+            serde_json::from_str(include_str!("../tests/data/proof_data.json")).unwrap();
+        let field = |name: &str| json[name].as_str().unwrap();
+        let proof = json["accountProof"].as_array().unwrap();
+        let leaf = hex::decode(&proof.last().unwrap().as_str().unwrap()[2..]).unwrap();
+        let value = match decode_node(&leaf).unwrap() {
+            OwnedNode::Leaf { value, .. } => value,
+            node => panic!("expected the account leaf, got {:?}", node),
+        };
+
+        let encoded = encode_account_leaf(
+            u64::from_str_radix(&field("nonce")[2..], 16).unwrap(),
+            field("balance").parse().unwrap(),
+            field("storageHash").parse().unwrap(),
+            field("codeHash").parse().unwrap(),
+        );
+        assert_eq!(keccak256(&encoded), keccak256(&value));
+        assert_eq!(encoded, value);
+    }
+
+    #[test]
+    fn it_rejects_oversized_account_fields() {
+        let account = |nonce: &[u8], balance: &[u8]| {
+            let mut stream = RlpStream::new_list(4);
+            stream.append(&nonce);
+            stream.append(&balance);
+            stream.append(&crate::EMPTY_TRIE_ROOT.as_slice());
+            stream.append(&B256::repeat_byte(0x22).as_slice());
+            stream.out().to_vec()
+        };
+
+        let decoded = AccountState::rlp_decode(&account(&[0xff; 8], &[0xff; 32])).unwrap();
+        assert_eq!(decoded.nonce, u64::MAX);
+        assert_eq!(decoded.balance, U256::MAX);
+        assert_eq!(
+            AccountState::rlp_decode(&account(&[0x01], &[0x01; 33])),
+            Err(DecoderError::RlpIsTooBig)
+        );
+        assert_eq!(
+            AccountState::rlp_decode(&account(&[0x01; 9], &[0x01])),
+            Err(DecoderError::RlpIsTooBig)
+        );
+        // Leading zeros would let a 33-byte balance fit once trimmed.
+        let mut padded = vec![0x00];
+        padded.extend_from_slice(&[0xff; 32]);
+        assert_eq!(
+            AccountState::rlp_decode(&account(&[0x01], &padded)),
+            Err(DecoderError::RlpIsTooBig)
+        );
+        assert_eq!(
+            AccountState::rlp_decode(&account(&[0x01], &[0x00, 0x01])),
+            Err(DecoderError::RlpInvalidIndirection)
+        );
+    }
+
+    #[test]
+    fn it_validates_proof_nodes_on_construction() {
+        let entries = vec![
+            (b"key-1".to_vec(), vec![0xab; 40]),
+            (b"key-2".to_vec(), vec![0xcd; 40]),
+        ];
+        let (db, root) = crate::tests::test_trie::<EthereumLayout>(&entries);
+        let (nodes, _) = crate::generate_proof::<EthereumLayout>(&db, &root, b"key-1").unwrap();
+
+        let proof = Proof::try_new(nodes.clone()).unwrap();
+        assert_eq!(proof.root_hash(), root);
+        crate::verify_proof_raw_key::<EthereumLayout>(&root, &proof, b"key-1", Some(&[0xab; 40]))
+            .expect("Failed to verify validated proof");
+        assert_eq!(proof.into_nodes(), nodes);
+
+        assert_eq!(Proof::try_new(Vec::new()), Err(VerifyError::IncompleteProof));
+        let mut truncated = nodes;
+        let last = truncated.len() - 1;
+        truncated[last].pop();
+        assert!(matches!(
+            Proof::try_new(truncated),
+            Err(VerifyError::DecodeError { depth, .. }) if depth == last
+        ));
+    }
+
+    #[test]
+    fn it_round_trips_proof_through_rlp_blob() {
+        let proof = vec![vec![0xc2, 0x20, 0x01], vec![0x01], Vec::new(), vec![0xab; 100]];
+        let blob = encode_rlp_proof(&proof);
+        assert!(Rlp::new(&blob).is_list());
+        assert_eq!(decode_rlp_proof(&blob), Ok(proof));
+        assert_eq!(decode_rlp_proof(&encode_rlp_proof(&[])), Ok(Vec::new()));
+
+        assert_eq!(decode_rlp_proof(&[0x82, 0x01, 0x02]), Err(DecoderError::RlpExpectedToBeList));
+        assert_eq!(
+            decode_rlp_proof(&[0xc2, 0xc1, 0x80]),
+            Err(DecoderError::RlpExpectedToBeData)
+        );
+        let mut trailing = blob.clone();
+        trailing.push(0x80);
+        assert_eq!(
+            decode_rlp_proof(&trailing),
+            Err(DecoderError::RlpInconsistentLengthAndData)
+        );
+    }
+
+    #[test]
+    fn it_verifies_proof_transported_as_rlp_blob() {
+        let entries: Vec<_> = (0..16u8).map(|i| (vec![i; 20], vec![i; 40])).collect();
+        let (db, root) = crate::tests::test_trie::<EthereumLayout>(&entries);
+        let (key, value) = &entries[4];
+        let (proof, _) = crate::generate_proof::<EthereumLayout>(&db, &root, key).unwrap();
+
+        let decoded = decode_rlp_proof(&encode_rlp_proof(&proof)).unwrap();
+        assert_eq!(decoded, proof);
+        crate::verify_proof_raw_key::<EthereumLayout>(&root, &decoded, key, Some(value))
+            .expect("Failed to verify proof decoded from RLP blob");
+    }
+
+    #[test]
+    fn it_encodes_zero_storage_value_as_empty_string() {
+        assert_eq!(rlp_encode_storage_value(&U256::ZERO), vec![0x80]);
+    }
+
+    #[test]
+    fn it_encodes_single_byte_storage_value() {
+        assert_eq!(rlp_encode_storage_value(&U256::from(0x01)), vec![0x01]);
+        assert_eq!(rlp_encode_storage_value(&U256::from(0x7f)), vec![0x7f]);
+        assert_eq!(rlp_encode_storage_value(&U256::from(0x80)), vec![0x81, 0x80]);
+    }
+
+    #[test]
+    fn it_encodes_max_storage_value() {
+        let mut expected = vec![0xa0];
+        expected.extend_from_slice(&[0xff; 32]);
+        assert_eq!(rlp_encode_storage_value(&U256::MAX), expected);
+    }
+
+    #[test]
+    fn it_trims_leading_zeros_from_storage_value() {
+        let value = U256::try_from_be_slice(&[0x00, 0xff]).unwrap();
+        assert_eq!(rlp_encode_storage_value(&value), vec![0x81, 0xff]);
+
+        let value = U256::from_be_bytes(B256::with_last_byte(0x01).0);
+        assert_eq!(rlp_encode_storage_value(&value), vec![0x01]);
+    }
+
+    #[test]
+    fn it_round_trips_storage_values() {
+        let values = [
+            U256::ZERO,
+            U256::from(0x7f),
+            U256::from(0x80),
+            U256::from_be_bytes(B256::with_last_byte(0x01).0),
+            U256::try_from_be_slice(&[0x01, 0x00, 0x00, 0x00]).unwrap(),
+            U256::MAX,
+        ];
+        for value in values {
+            let encoded = rlp_encode_storage_value(&value);
+            assert_eq!(rlp_decode_storage_value(&encoded), Ok(value));
+        }
+    }
+
+    #[test]
+    fn it_rejects_fixed_width_storage_values_when_decoding() {
+        let fixed = rlp_encode_storage_value_fixed(&U256::from(0x01));
+        assert_eq!(fixed.len(), 33);
+        assert_eq!(fixed[0], 0xa0);
+        assert_eq!(fixed[32], 0x01);
+        assert_eq!(
+            rlp_decode_storage_value(&fixed),
+            Err(DecoderError::RlpInvalidIndirection)
+        );
+        assert_eq!(
+            rlp_decode_storage_value(&[0x81, 0xff, 0x00]),
+            Err(DecoderError::RlpInconsistentLengthAndData)
+        );
+        assert_eq!(
+            rlp_encode_storage_value_fixed(&U256::MAX),
+            rlp_encode_storage_value(&U256::MAX)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_round_trips_account_state_through_serde() {
+        let account = AccountState {
+            nonce: 7,
+            balance: U256::from(0x1234),
+            storage_hash: B256::repeat_byte(0x33),
+            code_hash: B256::repeat_byte(0x22),
+        };
+
+        let json = serde_json::to_value(&account).unwrap();
+        assert_eq!(json["nonce"], "0x7");
+        assert_eq!(json["balance"], "0x1234");
+        assert_eq!(serde_json::from_value::<AccountState>(json).unwrap(), account);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_round_trips_proof_bundle_through_serde() {
+        let bundle = ProofBundle {
+            root: B256::repeat_byte(0x11),
+            key: vec![0xab; 20],
+            proof: vec![vec![0xc0], vec![0xde, 0xad]],
+            value: Some(vec![0x80]),
+        };
+
+        let json = serde_json::to_value(&bundle).unwrap();
+        assert_eq!(json["proof"], serde_json::json!(["0xc0", "0xdead"]));
+        assert_eq!(json["value"], "0x80");
+        assert_eq!(serde_json::from_value::<ProofBundle>(json).unwrap(), bundle);
+
+        let exclusion = ProofBundle { value: None, ..bundle };
+        let json = serde_json::to_string(&exclusion).unwrap();
+        assert_eq!(serde_json::from_str::<ProofBundle>(&json).unwrap(), exclusion);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_deserializes_account_state_from_eth_get_proof() {
+        let json = r#"{
+            "address": "0x3b2385025073625199d9edcf0612670f5b01fa6d",
+            "balance": "0x0",
+            "codeHash": "0x1e1706bdc2b9de10c4075b84a6181920bb73d94a161cb8044fc5d1c800030627",
+            "nonce": "0x1",
+            "storageHash": "0x0f460850d9716af3371839ff600d3d57ce12da330e95ac16f91da485fd8bd6c6"
+        }"#;
+
+        let account: AccountState = serde_json::from_str(json).unwrap();
+        assert_eq!(account.nonce, 1);
+        assert_eq!(account.balance, U256::ZERO);
+        assert_eq!(
+            account.storage_hash,
+            "0x0f460850d9716af3371839ff600d3d57ce12da330e95ac16f91da485fd8bd6c6"
+                .parse::<B256>()
+                .unwrap()
+        );
+        assert_eq!(
+            account.code_hash,
+            "0x1e1706bdc2b9de10c4075b84a6181920bb73d94a161cb8044fc5d1c800030627"
+                .parse::<B256>()
+                .unwrap()
+        );
+    }
+
+    #[cfg(feature = "revm")]
+    #[test]
+    fn it_round_trips_revm_account_info() {
+        use revm::primitives::AccountInfo;
+
+        let info = AccountInfo {
+            balance: U256::from(1_000_000),
+            nonce: 7,
+            code_hash: B256::repeat_byte(0x22),
+            code: None,
+        };
+        let storage_hash = B256::repeat_byte(0x33);
+
+        let account = AccountState::from_account_info(&info, storage_hash);
+        assert_eq!(account.storage_hash, storage_hash);
+        assert_eq!(AccountInfo::try_from(&account), Ok(info.clone()));
+
+        let account = AccountState::from(&info);
+        assert_eq!(account.storage_hash, crate::EMPTY_TRIE_ROOT);
+        assert_eq!(AccountInfo::try_from(&account), Ok(info));
+    }
+}
@@ -0,0 +1,237 @@
+use crate::rstd::vec::Vec;
+use crate::types::{IntoB256, B256};
+use crate::KeccakHasher;
+
+use hash_db::Hasher;
+use primitive_types::{H160, H256};
+use tiny_keccak::{Hasher as _, Keccak};
+
+/// Keccak-256 of `data`.
+pub fn keccak256(data: &[u8]) -> B256 {
+    KeccakHasher::hash(data)
+}
+
+/// The key of an account in the state trie: the keccak of its unpadded 20-byte address, as hashed
+/// by `SecTrieDBMut` and by [`crate::generate_proof`].
+pub fn account_key(address: &H160) -> B256 {
+    keccak256(address.as_bytes())
+}
+
+/// Convert a `primitive_types` hash to the alloy type used by the rest of the API. The bytes are
+/// kept in the same order.
+pub fn to_b256(hash: H256) -> B256 {
+    B256::from(hash.0)
+}
+
+/// Convert an alloy hash to its `primitive_types` equivalent, keeping the bytes in the same order.
+pub fn to_h256(hash: B256) -> H256 {
+    H256::from(hash.0)
+}
+
+/// The key of a storage slot in an account's storage trie: the keccak of the 32-byte slot, given as
+/// a `B256`, an `H256` or a `[u8; 32]`. This is the path storage proofs are verified under,
+/// whatever the slot holds.
+pub fn storage_trie_key(slot: impl IntoB256) -> B256 {
+    keccak256(slot.into_b256().as_slice())
+}
+
+/// The storage slot of the entry for `key` in a Solidity `mapping(address => ...)` declared at
+/// `slot`: the keccak of the key left-padded to 32 bytes followed by the slot. This locates the
+/// entry in the contract's storage layout; it is not a storage trie key. The entry is proven under
+/// `storage_trie_key(mapping_storage_slot(key, slot))`.
+pub fn mapping_storage_slot(key: &H160, slot: impl IntoB256) -> B256 {
+    let mut preimage = [0u8; 64];
+    preimage[12..32].copy_from_slice(key.as_bytes());
+    preimage[32..].copy_from_slice(slot.into_b256().as_slice());
+    keccak256(&preimage)
+}
+
+/// The storage slots of the entries for `key` in the mappings declared at each of `slots`, as
+/// [`mapping_storage_slot`]. The result has one slot per element of `slots`, in the same order:
+/// element `i` is `mapping_storage_slot(key, slots[i])`. The padded key is absorbed into the Keccak
+/// state once and the state is reused for every slot. This is the batch `calculate_storage_keys`,
+/// named after [`mapping_storage_slot`] because what it derives is a slot, not a storage trie key.
+pub fn mapping_storage_slots<S>(key: &H160, slots: &[S]) -> Vec<B256>
+where
+    S: IntoB256 + Copy,
+{
+    let mut padded_key = [0u8; 32];
+    padded_key[12..].copy_from_slice(key.as_bytes());
+    let mut prefix = Keccak::v256();
+    prefix.update(&padded_key);
+
+    slots
+        .iter()
+        .map(|slot| {
+            let mut keccak = prefix.clone();
+            keccak.update(slot.into_b256().as_slice());
+            let mut output = [0u8; 32];
+            keccak.finalize(&mut output);
+            B256::from(output)
+        })
+        .collect()
+}
+
+/// Why nibbles could not be packed into bytes by [`from_nibbles`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NibbleError {
+    /// There is an odd number of nibbles, which do not make whole bytes.
+    OddLength(usize),
+    /// The nibble at `index` is larger than `0xf`.
+    InvalidNibble { index: usize, nibble: u8 },
+}
+
+/// Split `bytes` into nibbles, high nibble first, as `NibbleSlice` reads trie paths. A 32-byte
+/// trie key gives the 64 nibbles of its path.
+pub fn to_nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|byte| [byte >> 4, byte & 0xf]).collect()
+}
+
+/// Pack nibbles back into bytes, high nibble first, the inverse of [`to_nibbles`].
+pub fn from_nibbles(nibbles: &[u8]) -> Result<Vec<u8>, NibbleError> {
+    if nibbles.len() % 2 != 0 {
+        return Err(NibbleError::OddLength(nibbles.len()));
+    }
+    if let Some(index) = nibbles.iter().position(|nibble| *nibble > 0xf) {
+        return Err(NibbleError::InvalidNibble {
+            index,
+            nibble: nibbles[index],
+        });
+    }
+    Ok(nibbles.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_codec::{decode_node, OwnedNode};
+    use crate::verify_proof_hashed_key;
+    use crate::types::U256;
+    use crate::EthereumLayout;
+
+    use serde_json::Value;
+
+    fn bytes(value: &Value) -> Vec<u8> {
+        hex::decode(value.as_str().unwrap().trim_start_matches("0x")).unwrap()
+    }
+
+    #[test]
+    fn it_derives_account_key_matching_proof_path() {
+        let json: Value =
+            serde_json::from_str(include_str!("../tests/data/proof_data.json")).unwrap();
+        let address = H160::from_slice(&bytes(&json["address"]));
+        let proof: Vec<Vec<u8>> = json["accountProof"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(bytes)
+            .collect();
+
+        let key = account_key(&address);
+        let (path, value) = match decode_node(proof.last().unwrap()).unwrap() {
+            OwnedNode::Leaf { path, value } => (path, value),
+            node => panic!("expected the account leaf, got {:?}", node),
+        };
+        assert!(to_nibbles(key.as_slice()).ends_with(&path));
+
+        let root = KeccakHasher::hash(&proof[0]);
+        verify_proof_hashed_key::<EthereumLayout>(&root, &proof, key.as_slice(), Some(&value))
+            .expect("Failed to verify account proof with derived key");
+    }
+
+    #[test]
+    fn it_splits_keys_into_nibbles() {
+        let key = keccak256(b"key");
+        let nibbles = to_nibbles(key.as_slice());
+        assert_eq!(nibbles.len(), 64);
+        assert_eq!(nibbles[0], key[0] >> 4);
+        assert_eq!(nibbles[1], key[0] & 0xf);
+        assert_eq!(from_nibbles(&nibbles), Ok(key.to_vec()));
+
+        assert_eq!(to_nibbles(&[0xab, 0x01]), vec![0xa, 0xb, 0x0, 0x1]);
+        assert_eq!(from_nibbles(&[]), Ok(Vec::new()));
+        assert_eq!(from_nibbles(&[0x1, 0x2, 0x3]), Err(NibbleError::OddLength(3)));
+        assert_eq!(
+            from_nibbles(&[0x1, 0x12]),
+            Err(NibbleError::InvalidNibble {
+                index: 1,
+                nibble: 0x12
+            })
+        );
+    }
+
+    #[test]
+    fn it_converts_hashes_keeping_byte_layout() {
+        let raw: [u8; 32] = core::array::from_fn(|index| index as u8);
+        let h256 = H256::from(raw);
+        let b256 = B256::from(raw);
+
+        assert_eq!(to_b256(h256), b256);
+        assert_eq!(to_h256(b256), h256);
+        assert_eq!(to_b256(h256).as_slice(), h256.as_bytes());
+        assert_eq!(
+            H256::from_low_u64_be(0x1234).as_bytes(),
+            &U256::from(0x1234).to_be_bytes::<32>()
+        );
+        assert_eq!(storage_trie_key(h256), storage_trie_key(b256));
+        assert_eq!(storage_trie_key(&h256), storage_trie_key(raw));
+    }
+
+    #[test]
+    fn it_derives_mapping_storage_slots_in_input_order() {
+        let key = H160::repeat_byte(0x42);
+        let slots: Vec<H256> = (0..100).map(H256::from_low_u64_be).collect();
+
+        let batch = mapping_storage_slots(&key, &slots);
+
+        assert_eq!(batch.len(), slots.len());
+        for (slot, derived) in slots.iter().zip(batch.iter()) {
+            assert_eq!(*derived, mapping_storage_slot(&key, slot));
+        }
+        assert_eq!(mapping_storage_slots::<B256>(&key, &[]), Vec::new());
+    }
+
+    #[test]
+    fn it_derives_storage_trie_key_matching_proof_path() {
+        let json: Value =
+            serde_json::from_str(include_str!("../tests/data/proof_data.json")).unwrap();
+        let address = H160::from_slice(&bytes(&json["address"]));
+        let storage_hash = bytes(&json["storageHash"]);
+        let slot_proof = &json["storageProof"][0];
+        let slot = H256::from_slice(&bytes(&slot_proof["key"]));
+        let proof: Vec<Vec<u8>> = slot_proof["proof"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(bytes)
+            .collect();
+        let value = rlp::encode(&bytes(&slot_proof["value"])).to_vec();
+        let root = B256::from_slice(&storage_hash);
+
+        let key = storage_trie_key(&slot);
+        assert_eq!(
+            key,
+            "0x290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e563"
+                .parse::<B256>()
+                .unwrap()
+        );
+        verify_proof_hashed_key::<EthereumLayout>(&root, &proof, key.as_slice(), Some(&value))
+            .expect("Failed to verify storage proof with derived key");
+
+        let mapping_slot = mapping_storage_slot(&address, &slot);
+        assert_eq!(
+            mapping_slot,
+            "0xfe5868f5216730c28717dec4e09d6329f79754f5a08ee4fb8fe3230d34bc0fdd"
+                .parse::<B256>()
+                .unwrap()
+        );
+        assert_eq!(mapping_storage_slot(&address, to_b256(slot)), mapping_slot);
+        assert!(verify_proof_hashed_key::<EthereumLayout>(
+            &root,
+            &proof,
+            mapping_slot.as_slice(),
+            Some(&value)
+        )
+        .is_err());
+    }
+}
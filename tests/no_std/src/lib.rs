@@ -0,0 +1,125 @@
+#![no_std]
+
+extern crate alloc;
+
+pub use ethereum_proofs::{verify_proof_fixed, verify_proofs};
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_proof_fixed, verify_proofs};
+
+    use alloc::{collections::BTreeMap, vec::Vec};
+    use ethereum_proofs::state::{StateProofInput, StorageProofInput};
+    use ethereum_proofs::types::{AccountState, Address, B256, U256};
+    use ethereum_proofs::utils::keccak256;
+    use ethereum_proofs::{verify_proof_hashed_key, EthereumLayout, VerifyError};
+    // This crate does not depend on trie-db, so these only resolve through the re-exports.
+    use ethereum_proofs::{CError, DBValue, Recorder, TrieHash, TrieLayout};
+    use serde_json::Value;
+
+    fn bytes(value: &Value) -> Vec<u8> {
+        hex::decode(value.as_str().unwrap().trim_start_matches("0x")).unwrap()
+    }
+
+    fn proof(value: &Value) -> Vec<Vec<u8>> {
+        value.as_array().unwrap().iter().map(bytes).collect()
+    }
+
+    fn fixture() -> (
+        BTreeMap<Address, AccountState>,
+        StateProofInput,
+        StorageProofInput,
+    ) {
+        let json: Value = serde_json::from_str(include_str!("../../data/proof_data.json")).unwrap();
+        let address: Address = json["address"].as_str().unwrap().parse().unwrap();
+        let account = AccountState {
+            nonce: u64::from_str_radix(
+                json["nonce"].as_str().unwrap().trim_start_matches("0x"),
+                16,
+            )
+            .unwrap(),
+            balance: json["balance"].as_str().unwrap().parse().unwrap(),
+            storage_hash: json["storageHash"].as_str().unwrap().parse().unwrap(),
+            code_hash: json["codeHash"].as_str().unwrap().parse().unwrap(),
+        };
+
+        let storage = json["storageProof"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|slot| {
+                let key: B256 = slot["key"].as_str().unwrap().parse().unwrap();
+                let value: U256 = slot["value"].as_str().unwrap().parse().unwrap();
+                (key, (value, proof(&slot["proof"])))
+            })
+            .collect();
+
+        (
+            BTreeMap::from([(address, account)]),
+            BTreeMap::from([(address, proof(&json["accountProof"]))]),
+            BTreeMap::from([(address, storage)]),
+        )
+    }
+
+    #[test]
+    fn it_verifies_fixture_proofs_without_std() {
+        let (state_tree, state_proof_input, storage_proof_input) = fixture();
+
+        let state_root = verify_proofs(&state_tree, state_proof_input, storage_proof_input)
+            .unwrap_or_else(|(address, _)| panic!("invalid proofs for {:?}", address));
+
+        let expected: B256 = "0x16a116384acc08d85d4e0304a4323304205d1f1eb49457bd15d83362d81a2d44"
+            .parse()
+            .unwrap();
+        assert_eq!(state_root, expected);
+    }
+
+    fn verify_generic<L: TrieLayout>(
+        root: &TrieHash<L>,
+        proof: &[DBValue],
+        key: &[u8],
+        expected: Option<&[u8]>,
+    ) -> Result<(), VerifyError<TrieHash<L>, CError<L>>> {
+        verify_proof_hashed_key::<L>(root, proof, key, expected)
+    }
+
+    #[test]
+    fn it_verifies_proof_with_reexported_trie_types() {
+        let (state_tree, state_proof_input, _) = fixture();
+        let (address, account) = state_tree.iter().next().unwrap();
+        let proof = &state_proof_input[address];
+        let root = keccak256(&proof[0]);
+        let key = keccak256(address.as_slice());
+
+        let value = account.rlp_encode();
+        let verified = verify_generic::<EthereumLayout>(&root, proof, key.as_slice(), Some(&value));
+        assert!(verified.is_ok());
+        let mut recorder = Recorder::<EthereumLayout>::new();
+        assert!(recorder.drain().is_empty());
+    }
+
+    #[test]
+    fn it_verifies_fixed_key_proof_as_allocating_path() {
+        let (state_tree, state_proof_input, _) = fixture();
+        let (address, account) = state_tree.iter().next().unwrap();
+        let proof = &state_proof_input[address];
+        let nodes: Vec<&[u8]> = proof.iter().map(Vec::as_slice).collect();
+        let root: [u8; 32] = keccak256(&proof[0]).0;
+        let key = keccak256(address.as_slice()).0;
+        let value = account.rlp_encode();
+        let mut other = value.clone();
+        other[4] ^= 1;
+
+        for expected in [Some(value.as_slice()), Some(other.as_slice()), None] {
+            let fixed = verify_proof_fixed::<EthereumLayout>(&root, &nodes, &key, expected);
+            let allocating =
+                verify_proof_hashed_key::<EthereumLayout>(&root.into(), proof, &key, expected);
+            // `VerifyError` is only `Debug` with `std`.
+            assert!(fixed == allocating);
+        }
+        assert!(verify_proof_fixed::<EthereumLayout>(&root, &nodes, &key, Some(&value)).is_ok());
+        assert!(
+            verify_proof_fixed::<EthereumLayout>(&root, &nodes[1..], &key, Some(&value)).is_err()
+        );
+    }
+}
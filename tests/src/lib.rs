@@ -13,37 +13,56 @@ pub fn read_json_file<T: DeserializeOwned>(
 #[cfg(test)]
 mod tests {
     use crate::read_json_file;
-    use drosera_core::primitives::{AccountInfoAll, TraceAccountState};
-    use drosera_core::{alloy_primitives::hex, utils::str_to_revm_address};
-    use drosera_prover::utils;
-    use ethers::types::EIP1186ProofResponse;
+    use ethereum_proofs::types::{AccountState, Address, EIP1186ProofResponse, B256};
+    use ethereum_proofs::{split_proofs, verify_proofs};
+    use serde_json::Value;
     use std::collections::{BTreeMap, HashMap};
 
-    use drosera_ethereum_proofs::verify_proofs;
-
     #[test]
     fn it_verifies_proofs() {
-        let trace_data: HashMap<String, TraceAccountState> =
+        let trace_data: HashMap<String, Value> =
             read_json_file("./data/trace_data.json").expect("Failed to read trace data");
 
         // Build state tree from trace data for zkevm
         let mut state_tree = BTreeMap::new();
         trace_data.into_iter().for_each(|(address, state)| {
-            let value: AccountInfoAll = state.into();
-            let key = str_to_revm_address(address.as_str());
+            let storage = state["storage"]
+                .as_object()
+                .unwrap()
+                .iter()
+                .map(|(slot, value)| {
+                    (slot.parse().unwrap(), value.as_str().unwrap().parse().unwrap())
+                })
+                .collect();
+            let code = state["code"].as_str().unwrap().trim_start_matches("0x");
+            let code = hex::decode(code).unwrap();
+            let value = AccountState::from_contents(
+                state["nonce"].as_u64().unwrap(),
+                state["balance"].as_str().unwrap().parse().unwrap(),
+                &code,
+                &storage,
+            );
+            let key: Address = address.parse().unwrap();
             state_tree.insert(key, value);
         });
 
         let proofs: EIP1186ProofResponse =
             read_json_file("./data/proof_data.json").expect("Failed to read proofs");
 
-        let (state_proof_input, storage_proof_input) = utils::parse_proof_inputs(vec![proofs]);
+        let mut state_proof_input = BTreeMap::new();
+        let mut storage_proof_input = BTreeMap::new();
+        for account in split_proofs(vec![proofs]) {
+            assert_eq!(state_tree[&account.address], account.account);
+            state_proof_input.insert(account.address, account.account_proof);
+            let slots = account.slots.into_iter().map(|slot| (slot.slot, (slot.value, slot.proof)));
+            storage_proof_input.insert(account.address, slots.collect());
+        }
 
         let state_root = verify_proofs(&state_tree, state_proof_input, storage_proof_input);
 
-        assert_eq!(
-            state_root,
-            hex!["16a116384acc08d85d4e0304a4323304205d1f1eb49457bd15d83362d81a2d44"]
-        );
+        let expected: B256 = "0x16a116384acc08d85d4e0304a4323304205d1f1eb49457bd15d83362d81a2d44"
+            .parse()
+            .unwrap();
+        assert_eq!(state_root, Ok(expected));
     }
 }